@@ -1,12 +1,33 @@
+use chrono::{Local, NaiveDate};
 use exitfailure::ExitFailure;
 use failure::ResultExt;
 use log::{debug, error, info, trace, warn};
-use myrello::cli_opt::{Cmd, DbCmd, TaskCmd};
-use myrello::cli_opt::{ShowCmd, ShowOpt, StepCmd};
+use myrello::chart;
+use myrello::cli_opt::{mine_or_assignee, ShowCmd, ShowOpt, SprintCmd, StepCmd};
+use myrello::cli_opt::{Cmd, DbCmd, ExportCmd, ImportCmd, ImportCommonOpt, LabelCmd, TaskCmd};
+use myrello::config;
+use myrello::config::{CompleteTask, Config};
 use myrello::db;
+use myrello::gitsync;
+use myrello::hooks::{Event, Hooks, When};
+use myrello::metrics;
+use myrello::metrics::VelocityBy;
+use myrello::op;
+use myrello::output;
+use myrello::report;
+use myrello::sort::SortSpec;
 use myrello::task;
-use myrello::task::TimeWindow;
+use myrello::task::{DateRange, TimeWindow};
+use myrello::transfer;
+use myrello::transfer::backup;
+use myrello::transfer::csv;
+use myrello::transfer::ical;
+use myrello::transfer::markdown;
+use myrello::transfer::taskwarrior;
+use myrello::transfer::todotxt;
+use myrello::transfer::trello;
 use rusqlite::Connection;
+use std::io::Write;
 use std::path::PathBuf;
 use structopt::clap::Shell;
 use structopt::StructOpt;
@@ -19,6 +40,9 @@ struct Opt {
     /// Specify the database file you want to use
     #[structopt(short = "d", long = "db", parse(from_os_str), raw(global = "true"))]
     dbfile: Option<PathBuf>,
+    /// Specify the configuration file you want to use
+    #[structopt(short = "c", long = "config", parse(from_os_str), raw(global = "true"))]
+    configfile: Option<PathBuf>,
     #[structopt(subcommand)]
     cmd: Cmd,
 }
@@ -57,35 +81,57 @@ fn cmd_dbinit(dbfile: std::path::PathBuf, force: bool) -> Result<(), ExitFailure
     Ok(())
 }
 
-fn cmd_show(mut showopt: ShowOpt, dbfile: &std::path::Path) -> Result<(), ExitFailure> {
+fn cmd_show(
+    mut showopt: ShowOpt,
+    dbfile: &std::path::Path,
+    config: &Config,
+) -> Result<(), ExitFailure> {
     debug!("show: showopt => {:?}", showopt);
     let db_connection = db::get_db(&dbfile)?;
     if let Some(task_id) = showopt.task {
-        task::show1task(&db_connection, task_id);
+        task::show1task(
+            &db_connection,
+            task_id,
+            showopt.show_opts.format.unwrap_or_default(),
+        );
     } else {
         let cmd = showopt.cmd.unwrap_or_else(|| ShowCmd::All {
             show_opts: Default::default(),
         });
         match cmd {
             ShowCmd::All { show_opts } => {
-                let tasks = db::get_open_tasks(&db_connection)?;
                 showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                showopt.show_opts.columns_or(&config.columns.all);
+                let tasks = db::get_open_tasks_where(
+                    &db_connection,
+                    showopt.show_opts.filter.as_ref(),
+                    &showopt.show_opts.sort_or(SortSpec::open_default()),
+                )?;
                 task::show2(&db_connection, &tasks, showopt.show_opts.as_show_params(""));
             }
             ShowCmd::Short { show_opts } => {
-                let tasks = db::get_open_tasks(&db_connection)?;
                 showopt.show_opts.merge(&show_opts);
-                task::show_short(
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                showopt.show_opts.columns_or(&config.columns.short);
+                let tasks = db::get_open_tasks_where(
                     &db_connection,
-                    &tasks,
-                    &showopt.show_opts.labels,
-                    showopt.show_opts.reference,
-                    showopt.show_opts.hidden,
-                );
+                    showopt.show_opts.filter.as_ref(),
+                    &showopt.show_opts.sort_or(SortSpec::open_default()),
+                )?;
+                let mut show_param = showopt.show_opts.as_show_params("");
+                show_param.steps = true;
+                task::show_short(&db_connection, &tasks, show_param);
             }
             ShowCmd::Backlog { show_opts } => {
-                let tasks = db::get_open_tasks(&db_connection)?;
                 showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                showopt.show_opts.columns_or(&config.columns.backlog);
+                let tasks = db::get_open_tasks_where(
+                    &db_connection,
+                    showopt.show_opts.filter.as_ref(),
+                    &showopt.show_opts.sort_or(SortSpec::open_default()),
+                )?;
                 task::show2(
                     &db_connection,
                     &tasks,
@@ -93,8 +139,14 @@ fn cmd_show(mut showopt: ShowOpt, dbfile: &std::path::Path) -> Result<(), ExitFa
                 );
             }
             ShowCmd::Work { show_opts } => {
-                let tasks = db::get_open_tasks(&db_connection)?;
                 showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                showopt.show_opts.columns_or(&config.columns.work);
+                let tasks = db::get_open_tasks_where(
+                    &db_connection,
+                    showopt.show_opts.filter.as_ref(),
+                    &showopt.show_opts.sort_or(SortSpec::open_default()),
+                )?;
                 let mut show_param = showopt.show_opts.as_show_params("in_progress");
                 show_param.steps = true;
                 task::show2(&db_connection, &tasks, show_param);
@@ -102,16 +154,257 @@ fn cmd_show(mut showopt: ShowOpt, dbfile: &std::path::Path) -> Result<(), ExitFa
             ShowCmd::Done {
                 show_opts,
                 time_window,
+                since,
+                until,
             } => {
-                let tasks = db::get_done_tasks(&db_connection)?;
                 showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                showopt.show_opts.columns_or(&config.columns.done);
+                let range = completion_range(time_window, since, until, TimeWindow::Today);
+                let tasks = db::get_done_tasks_where(
+                    &db_connection,
+                    showopt.show_opts.filter.as_ref(),
+                    &range,
+                    &showopt.show_opts.sort_or(SortSpec::done_default()),
+                )?;
                 task::show_done(
                     &db_connection,
                     &tasks,
-                    &showopt.show_opts.labels,
-                    &time_window.unwrap_or(TimeWindow::Today),
-                    showopt.show_opts.reference,
-                    showopt.show_opts.hidden,
+                    showopt.show_opts.as_show_params("done"),
+                );
+            }
+            ShowCmd::Sprint { show_opts, sprint } => {
+                showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                showopt.show_opts.columns_or(&config.columns.sprint);
+                let sprint = get_sprint_or_active(&db_connection, sprint)?;
+                let mut tasks = db::get_sprint_tasks(
+                    &db_connection,
+                    sprint.id,
+                    &showopt.show_opts.sort_or(SortSpec::open_default()),
+                )?;
+                if let Some(filter) = &showopt.show_opts.filter {
+                    tasks.retain(|(t, _)| {
+                        let labels = db::get_labels(&db_connection, t.id).unwrap_or_default();
+                        filter.eval(t, &labels)
+                    });
+                }
+                task::show_sprint(
+                    &db_connection,
+                    &sprint,
+                    &tasks,
+                    showopt.show_opts.as_show_params(""),
+                );
+            }
+            ShowCmd::Velocity {
+                show_opts,
+                by,
+                number,
+                average,
+                by_label,
+            } => {
+                showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                let show_param = showopt.show_opts.as_show_params("done");
+                let filter = showopt.show_opts.filter.as_ref();
+                let mut buckets = match by {
+                    VelocityBy::Week => {
+                        metrics::velocity_weeks(&db_connection, filter, &show_param, number)?
+                    }
+                    VelocityBy::Sprint => {
+                        metrics::velocity_sprints(&db_connection, filter, &show_param, number)?
+                    }
+                };
+                metrics::set_rolling_average(&mut buckets, average);
+                metrics::show_velocity(&buckets, by, by_label, show_param.format);
+            }
+            ShowCmd::Flow {
+                show_opts,
+                time_window,
+                since,
+                until,
+                slowest,
+            } => {
+                showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                let show_param = showopt.show_opts.as_show_params("done");
+                let range = completion_range(time_window, since, until, TimeWindow::Month);
+                let items = metrics::flow_items(
+                    &db_connection,
+                    showopt.show_opts.filter.as_ref(),
+                    &show_param,
+                    &range,
+                )?;
+                metrics::show_flow(&metrics::flow_report(&items, slowest), show_param.format);
+            }
+            ShowCmd::Chart {
+                show_opts,
+                kind,
+                sprint,
+                since,
+                style,
+                width,
+                height,
+                count_tasks,
+                svg,
+            } => {
+                showopt.show_opts.merge(&show_opts);
+                showopt.show_opts.resolve_mine(config.current_user())?;
+                let show_param = showopt.show_opts.as_show_params("");
+                let filter = showopt.show_opts.filter.as_ref();
+                let today = Local::today().naive_local();
+                let (title, tasks, (first, last)) = match sprint {
+                    Some(sprint) => {
+                        let sprint = get_sprint_or_active(&db_connection, sprint)?;
+                        let tasks = chart::sprint_tasks(
+                            &db_connection,
+                            &sprint,
+                            filter,
+                            &show_param,
+                            count_tasks,
+                        )?;
+                        let days = match chart::sprint_days(&sprint, today) {
+                            Some(days) => days,
+                            None => {
+                                error!("The dates of the sprint {} are not valid", sprint.id);
+                                return Ok(());
+                            }
+                        };
+                        (
+                            format!("Sprint {}: {}", sprint.id, sprint.name),
+                            tasks,
+                            days,
+                        )
+                    }
+                    None => {
+                        let tasks =
+                            chart::board_tasks(&db_connection, filter, &show_param, count_tasks)?;
+                        let (default_first, last) = chart::board_days(today);
+                        let title = if show_param.label.is_empty() {
+                            "Board".to_string()
+                        } else {
+                            format!("Labels: {}", show_param.label.join(", "))
+                        };
+                        (title, tasks, (since.unwrap_or(default_first), last))
+                    }
+                };
+                let points = chart::series(&tasks, first, last);
+                let title = format!(
+                    "{} - {} of the {}",
+                    title,
+                    kind,
+                    if count_tasks { "tasks" } else { "story points" }
+                );
+                if svg {
+                    print!("{}", chart::render_svg(&points, kind, &title));
+                } else {
+                    println!("{}", title);
+                    print!(
+                        "{}",
+                        chart::render_ascii(
+                            &points,
+                            kind,
+                            style,
+                            width.map_or_else(chart::terminal_width, usize::from),
+                            usize::from(height),
+                        )
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The completion range: the explicit dates if any, or the time window
+fn completion_range(
+    time_window: Option<TimeWindow>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    default: TimeWindow,
+) -> DateRange {
+    if since.is_some() || until.is_some() {
+        DateRange::from_local_days(since, until)
+    } else {
+        time_window.unwrap_or(default).range()
+    }
+}
+
+fn get_sprint_or_active(
+    db_connection: &Connection,
+    sprint: Option<u32>,
+) -> Result<task::Sprint, ExitFailure> {
+    let sprint = match sprint {
+        Some(sprint_id) => db::get_sprint(db_connection, sprint_id)
+            .with_context(|_| format!("Failed to find sprint {}", sprint_id))?,
+        None => db::get_active_sprint(db_connection)
+            .with_context(|_| "Failed to find an active sprint".to_string())?,
+    };
+    Ok(sprint)
+}
+
+fn cmd_sprint(sprintcmd: SprintCmd, db_connection: &Connection) -> Result<(), ExitFailure> {
+    match sprintcmd {
+        SprintCmd::New {
+            start,
+            end,
+            capacity,
+            name,
+        } => {
+            let text = descr_to_string(&name);
+            let start = start.unwrap_or_else(|| Local::today().naive_local());
+            if end < start {
+                error!("The sprint cannot end before it starts");
+                return Ok(());
+            }
+            info!("add a sprint {} from {} to {}", text, start, end);
+            let new_id = db::add_sprint(db_connection, &text, &start, &end, capacity)
+                .with_context(|_| format!("Failed to create the new sprint {}", text))?;
+            println!("Create a new sprint, with id {}", new_id);
+        }
+        SprintCmd::Add { sprint, task } => {
+            let sprint = get_sprint_or_active(db_connection, sprint)?;
+            info!("Add task {} to sprint {}", task, sprint.id);
+            db::add_sprint_task(db_connection, sprint.id, task)
+                .with_context(|_| format!("Failed to add task {} to sprint {}", task, sprint.id))?;
+        }
+        SprintCmd::Remove { sprint, task } => {
+            let sprint = get_sprint_or_active(db_connection, sprint)?;
+            info!("Remove task {} from sprint {}", task, sprint.id);
+            db::remove_sprint_task(db_connection, sprint.id, task).with_context(|_| {
+                format!("Failed to remove task {} from sprint {}", task, sprint.id)
+            })?;
+        }
+        SprintCmd::Complete { sprint, next } => {
+            let sprint = get_sprint_or_active(db_connection, sprint)?;
+            info!("Complete sprint {}", sprint.id);
+            match db::complete_sprint(db_connection, sprint.id, next)
+                .with_context(|_| format!("Failed to complete sprint {}", sprint.id))?
+            {
+                Some(next) => println!(
+                    "Sprint {} completed, unfinished tasks moved to sprint {}",
+                    sprint.id, next
+                ),
+                None => println!(
+                    "Sprint {} completed, no next sprint for the unfinished tasks",
+                    sprint.id
+                ),
+            }
+        }
+        SprintCmd::List => {
+            for sprint in db::get_sprints(db_connection)? {
+                println!(
+                    "{}: {} [{} - {}] capacity {}{}",
+                    sprint.id,
+                    sprint.name,
+                    sprint.start_date,
+                    sprint.end_date,
+                    sprint.capacity,
+                    if sprint.closed_date.is_some() {
+                        " closed"
+                    } else {
+                        ""
+                    }
                 );
             }
         }
@@ -119,17 +412,37 @@ fn cmd_show(mut showopt: ShowOpt, dbfile: &std::path::Path) -> Result<(), ExitFa
     Ok(())
 }
 
-fn cmd_task_new(new_task: TaskCmd, db_connection: &Connection) -> Result<(), ExitFailure> {
+fn cmd_task_new(
+    new_task: TaskCmd,
+    db_connection: &Connection,
+    hooks: &Hooks,
+) -> Result<(), ExitFailure> {
     if let TaskCmd::New {
         labels,
         priority,
         storypoint,
         reference,
+        due,
         descr,
     } = new_task
     {
         let text = descr_to_string(&descr);
         info!("add a task with description {}", text);
+        db::check_label_rules(db_connection, &[], &labels)?;
+        if hooks.find(When::Pre, Event::Add).is_some() {
+            // the task doesn't exist yet, it has no id
+            let new_task = op::Task {
+                descr: text.trim_end().to_string(),
+                priority: priority.clone().unwrap_or_else(|| "normal".to_string()),
+                status: "todo".to_string(),
+                storypoints: storypoint.unwrap_or_default(),
+                labels: labels.clone(),
+                reference: reference.clone(),
+                due_date: due.map(|d| d.format("%Y-%m-%d").to_string()),
+                ..Default::default()
+            };
+            run_hook_task(hooks, When::Pre, Event::Add, &new_task)?;
+        }
         let new_id = db::add_task(db_connection, &text)
             .with_context(|_| format!("Failed to create the new task {}", text))?;
         if !labels.is_empty() {
@@ -168,19 +481,34 @@ fn cmd_task_new(new_task: TaskCmd, db_connection: &Connection) -> Result<(), Exi
                 )
             })?;
         }
+        if let Some(due_date) = due {
+            debug!("set due date {}", due_date);
+            db::set_due_date(db_connection, new_id, Some(&due_date)).with_context(|_| {
+                format!(
+                    "Failed to set due date {} to the new task {}",
+                    due_date, new_id
+                )
+            })?;
+        }
         db::add_step(&db_connection, new_id, "start")?;
         println!("Create a new task, with id {}", new_id);
+        run_hook(hooks, When::On, Event::Add, db_connection, new_id)?;
     }
     Ok(())
 }
 
-fn cmd_task_edit(edit_task: TaskCmd, db_connection: &Connection) -> Result<(), ExitFailure> {
+fn cmd_task_edit(
+    edit_task: TaskCmd,
+    db_connection: &Connection,
+    hooks: &Hooks,
+) -> Result<(), ExitFailure> {
     if let TaskCmd::Edit {
         task,
         priority,
         reference,
         status,
         storypoint,
+        due,
         descr,
     } = edit_task
     {
@@ -189,6 +517,7 @@ fn cmd_task_edit(edit_task: TaskCmd, db_connection: &Connection) -> Result<(), E
             && descr.is_empty()
             && storypoint.is_none()
             && reference.is_none()
+            && due.is_none()
         {
             error!("You have to specify at least on attribute you want to edit");
         } else {
@@ -219,22 +548,219 @@ fn cmd_task_edit(edit_task: TaskCmd, db_connection: &Connection) -> Result<(), E
                     format!("Failed to edit task {} with reference {}", task, ref_str)
                 })?;
             }
-            if let Some(status) = status {
-                if status == "done" {
-                    warn!("To make a task as done, please use the command task-done");
-                    db::complete_task(&db_connection, task).with_context(|_| {
-                        format!("Failed to edit task {} with status done", task)
+            if let Some(due_date) = due {
+                debug!("set due date {}", due_date);
+                db::set_due_date(db_connection, task, Some(&due_date)).with_context(|_| {
+                    format!("Failed to edit task {} with due date {}", task, due_date)
+                })?;
+            }
+            // the status changes with an event run its hooks, as the commands do
+            match status.as_deref() {
+                Some("done") => cmd_task_done(db_connection, task, hooks)?,
+                Some("in_progress") => cmd_task_start(db_connection, task, hooks)?,
+                Some("block") => cmd_task_block(db_connection, task, hooks)?,
+                Some(status) => {
+                    db::set_status(&db_connection, task, status).with_context(|_| {
+                        format!("Failed to edit task {} with status {}", task, status)
                     })?;
                 }
-                db::set_status(&db_connection, task, &status).with_context(|_| {
-                    format!("Failed to edit task {} with status {}", task, status)
-                })?;
+                None => {}
             }
         }
     }
 
     Ok(())
 }
+
+fn cmd_task_start(
+    db_connection: &Connection,
+    task_id: u32,
+    hooks: &Hooks,
+) -> Result<(), ExitFailure> {
+    run_hook(hooks, When::Pre, Event::Start, db_connection, task_id)?;
+    info!("Start task {}", task_id);
+    db::set_status(db_connection, task_id, "in_progress")
+        .with_context(|_| format!("Failed to start task {}", task_id))?;
+    if db::get_step(db_connection, task_id, 0).is_ok() {
+        db::complete_step(db_connection, task_id, 0)
+            .with_context(|_| format!("Failed to close the first step of task {}", task_id))?;
+    }
+    run_hook(hooks, When::On, Event::Start, db_connection, task_id)?;
+    Ok(())
+}
+
+fn cmd_task_block(
+    db_connection: &Connection,
+    task_id: u32,
+    hooks: &Hooks,
+) -> Result<(), ExitFailure> {
+    run_hook(hooks, When::Pre, Event::Block, db_connection, task_id)?;
+    info!("Block task {}", task_id);
+    db::set_status(db_connection, task_id, "block")
+        .with_context(|_| format!("Failed to block task {}", task_id))?;
+    run_hook(hooks, When::On, Event::Block, db_connection, task_id)?;
+    Ok(())
+}
+
+fn cmd_task_done(
+    db_connection: &Connection,
+    task_id: u32,
+    hooks: &Hooks,
+) -> Result<(), ExitFailure> {
+    run_hook(hooks, When::Pre, Event::Done, db_connection, task_id)?;
+    info!("Completed task {}", task_id);
+    db::complete_task(db_connection, task_id)?;
+    db::set_status(db_connection, task_id, "done")
+        .with_context(|_| format!("Failed to complete task {}", task_id))?;
+    db::complete_steps(db_connection, task_id)?;
+    run_hook(hooks, When::On, Event::Done, db_connection, task_id)?;
+    Ok(())
+}
+
+fn cmd_step_done(
+    db_connection: &Connection,
+    task_id: u32,
+    step_id: u32,
+    complete_task: CompleteTask,
+    hooks: &Hooks,
+) -> Result<(), ExitFailure> {
+    let event = Event::StepDone(step_id);
+    run_hook(hooks, When::Pre, event, db_connection, task_id)?;
+    info!("Done step {} of task {}", step_id, task_id);
+    db::complete_step(db_connection, task_id, step_id)
+        .with_context(|_| format!("Failed to close the step {} of task {}", step_id, task_id))?;
+    run_hook(hooks, When::On, event, db_connection, task_id)?;
+    if !db::all_steps_done(db_connection, task_id)?
+        || db::get_task_status(db_connection, task_id)? == "done"
+    {
+        return Ok(());
+    }
+    let confirmed = match complete_task {
+        CompleteTask::Auto => true,
+        CompleteTask::Never => false,
+        CompleteTask::Prompt => {
+            print!(
+                "All the steps of task {} are done, complete the task? [y/N] ",
+                task_id
+            );
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes")
+        }
+    };
+    if confirmed {
+        cmd_task_done(db_connection, task_id, hooks)?;
+        println!("Task {} completed", task_id);
+    }
+    Ok(())
+}
+
+fn cmd_step_reopen(
+    db_connection: &Connection,
+    task_id: u32,
+    step_id: u32,
+) -> Result<(), ExitFailure> {
+    info!("Reopen step {} of task {}", step_id, task_id);
+    db::reopen_step(db_connection, task_id, step_id)
+        .with_context(|_| format!("Failed to reopen the step {} of task {}", step_id, task_id))?;
+    if db::get_task_status(db_connection, task_id)? == "done" {
+        db::reopen_task(db_connection, task_id)
+            .with_context(|_| format!("Failed to reopen task {}", task_id))?;
+        println!("Task {} reopened", task_id);
+    }
+    Ok(())
+}
+
+/// Run the hook of the event, if any, with the task as it is in the database
+fn run_hook(
+    hooks: &Hooks,
+    when: When,
+    event: Event,
+    db_connection: &Connection,
+    task_id: u32,
+) -> Result<(), ExitFailure> {
+    if hooks.find(when, event).is_none() {
+        return Ok(());
+    }
+    let task = task::get_op_task(db_connection, task_id)
+        .with_context(|_| format!("Failed to get task {}", task_id))?;
+    run_hook_task(hooks, when, event, &task)
+}
+
+/// Run the hook of the event, if any: a failing pre- hook aborts the command,
+/// a failing on- hook is only reported, the operation is already done
+fn run_hook_task(
+    hooks: &Hooks,
+    when: When,
+    event: Event,
+    task: &op::Task,
+) -> Result<(), ExitFailure> {
+    match hooks.run(when, event, task) {
+        Err(e) if when == When::On => {
+            error!("{}", e);
+            Ok(())
+        }
+        rv => Ok(rv?),
+    }
+}
+
+/// Show the tasks to be imported and, after the confirmation, create them in
+/// a single transaction
+/// The on-add hook runs for each task after the commit, the pre-add hook doesn't run
+fn cmd_import<F>(
+    dbfile: &std::path::Path,
+    tasks: &[transfer::NewTask],
+    import_opts: &ImportCommonOpt,
+    question: &str,
+    hooks: &Hooks,
+    create: F,
+) -> Result<(), ExitFailure>
+where
+    F: FnOnce(&Connection) -> Result<Vec<u32>, transfer::TransferError>,
+{
+    let (headers, rows) = transfer::preview(tasks);
+    output::print_report(output::Format::Table, &headers, &rows);
+    if import_opts.dry_run || tasks.is_empty() {
+        return Ok(());
+    }
+    if !import_opts.yes {
+        print!("{} [y/N] ", question);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !(answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes")) {
+            return Ok(());
+        }
+    }
+    let mut db_connection = db::get_db(dbfile)?;
+    let tx = db_connection.transaction()?;
+    let ids = create(&tx)?;
+    tx.commit()?;
+    for id in &ids {
+        run_hook(hooks, When::On, Event::Add, &db_connection, *id)?;
+    }
+    if let (Some(first), Some(last)) = (ids.iter().min(), ids.iter().max()) {
+        println!(
+            "Imported {} tasks, with ids {} to {}",
+            ids.len(),
+            first,
+            last
+        );
+    }
+    Ok(())
+}
+
+/// Write the output of an export to a file, or to the standard output
+fn write_output(output: &Option<PathBuf>, content: &str) -> Result<(), ExitFailure> {
+    match output {
+        Some(path) => std::fs::write(path, content)
+            .with_context(|_| format!("Failed to write {}", path.display()))?,
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
 fn descr_to_string(descr: &[String]) -> String {
     let mut rv = String::new();
     for x in descr {
@@ -254,6 +780,16 @@ fn main() -> Result<(), ExitFailure> {
         None => dbfile_default()?,
     };
     trace!("Using {:?} as database", dbfile);
+    let configfile = opt.configfile.unwrap_or_else(config::config_default);
+    trace!("Using {:?} as configuration", configfile);
+    let config = config::load(&configfile)?;
+    let hooks = Hooks::new(
+        &config
+            .hooks
+            .dir
+            .clone()
+            .unwrap_or_else(|| configfile.with_file_name("hooks")),
+    );
     match opt.cmd {
         Cmd::Completion => {
             Opt::clap().gen_completions_to("myrello", Shell::Zsh, &mut std::io::stdout());
@@ -269,6 +805,7 @@ fn main() -> Result<(), ExitFailure> {
                     priority,
                     storypoint,
                     reference,
+                    due,
                     descr,
                 } => {
                     cmd_task_new(
@@ -277,9 +814,11 @@ fn main() -> Result<(), ExitFailure> {
                             priority,
                             storypoint,
                             reference,
+                            due,
                             descr,
                         },
                         &db_connection,
+                        &hooks,
                     )?;
                 }
                 TaskCmd::AddLabel { labels, task } => {
@@ -297,6 +836,7 @@ fn main() -> Result<(), ExitFailure> {
                     reference,
                     status,
                     storypoint,
+                    due,
                     descr,
                 } => {
                     cmd_task_edit(
@@ -306,40 +846,62 @@ fn main() -> Result<(), ExitFailure> {
                             reference,
                             status,
                             storypoint,
+                            due,
                             descr,
                         },
                         &db_connection,
+                        &hooks,
                     )?;
                 }
                 TaskCmd::Start(task) => {
-                    info!("Start task {}", task.task_id);
-                    db::set_status(&db_connection, task.task_id, "in_progress")
-                        .with_context(|_| format!("Failed to start task {}", task.task_id))?;
-                    if db::get_step(&db_connection, task.task_id, 0).is_ok() {
-                        db::complete_step(&db_connection, task.task_id, 0).with_context(|_| {
-                            format!("Failed to close the first step of task {}", task.task_id)
-                        })?;
-                    }
+                    cmd_task_start(&db_connection, task.task_id, &hooks)?;
                 }
                 TaskCmd::Block(task) => {
-                    info!("Block task {}", task.task_id);
-                    db::set_status(&db_connection, task.task_id, "block")
-                        .with_context(|_| format!("Failed to block task {}", task.task_id))?;
+                    cmd_task_block(&db_connection, task.task_id, &hooks)?;
                 }
                 TaskCmd::Done(task) => {
-                    info!("Completed task {}", task.task_id);
-                    db::complete_task(&db_connection, task.task_id)?;
-                    db::set_status(&db_connection, task.task_id, "done")
-                        .with_context(|_| format!("Failed to complete task {}", task.task_id))?;
-                    db::complete_steps(&db_connection, task.task_id)?;
+                    cmd_task_done(&db_connection, task.task_id, &hooks)?;
                 }
                 TaskCmd::Delete(task) => {
+                    run_hook(
+                        &hooks,
+                        When::Pre,
+                        Event::Delete,
+                        &db_connection,
+                        task.task_id,
+                    )?;
+                    // the task is gone after the delete, the on-delete hook gets it as it was
+                    let deleted = match hooks.find(When::On, Event::Delete) {
+                        Some(_) => Some(task::get_op_task(&db_connection, task.task_id)?),
+                        None => None,
+                    };
                     info!("Delete task {}", task.task_id);
                     db::delete_steps(&db_connection, task.task_id).with_context(|_| {
                         format!("Failed to delete task {} steps", task.task_id)
                     })?;
                     db::delete_task(&db_connection, task.task_id)
                         .with_context(|_| format!("Failed to delete task {}", task.task_id))?;
+                    if let Some(deleted) = deleted {
+                        run_hook_task(&hooks, When::On, Event::Delete, &deleted)?;
+                    }
+                }
+                TaskCmd::Assign { task, assignee } => {
+                    let assignee = match assignee.or_else(|| config.current_user()) {
+                        Some(assignee) => assignee,
+                        None => {
+                            error!("No assignee specified and no current user configured");
+                            return Ok(());
+                        }
+                    };
+                    info!("Assign task {} to {}", task, assignee);
+                    db::set_assignee(&db_connection, task, Some(&assignee)).with_context(|_| {
+                        format!("Failed to assign task {} to {}", task, assignee)
+                    })?;
+                }
+                TaskCmd::Unassign(task) => {
+                    info!("Unassign task {}", task.task_id);
+                    db::set_assignee(&db_connection, task.task_id, None)
+                        .with_context(|_| format!("Failed to unassign task {}", task.task_id))?;
                 }
                 TaskCmd::Prio(task) => {
                     info!("Increase priority of task {}", task.task_id);
@@ -347,6 +909,17 @@ fn main() -> Result<(), ExitFailure> {
                         format!("Faile to increase priority of task {}", task.task_id)
                     })?;
                 }
+                TaskCmd::Note { task, note } => {
+                    let text = descr_to_string(&note);
+                    let note = if text.trim().is_empty() {
+                        None
+                    } else {
+                        Some(text.as_str())
+                    };
+                    info!("Set note of task {}", task);
+                    db::set_note(&db_connection, task, note)
+                        .with_context(|_| format!("Failed to set the note of task {}", task))?;
+                }
             }
         }
         Cmd::Step(stepcmd) => {
@@ -360,10 +933,16 @@ fn main() -> Result<(), ExitFailure> {
                     println!("Create a new step, with id {}", new_step_id);
                 }
                 StepCmd::Done { task_id, step_id } => {
-                    info!("Done step {} of task {}", step_id, task_id);
-                    db::complete_step(&db_connection, task_id, step_id).with_context(|_| {
-                        format!("Failed to close the step {} of task {}", step_id, task_id)
-                    })?;
+                    cmd_step_done(
+                        &db_connection,
+                        task_id,
+                        step_id,
+                        config.steps.complete_task,
+                        &hooks,
+                    )?;
+                }
+                StepCmd::Reopen { task_id, step_id } => {
+                    cmd_step_reopen(&db_connection, task_id, step_id)?;
                 }
                 StepCmd::Delete { task_id, step_id } => {
                     info!("Done step {} of task {}", step_id, task_id);
@@ -371,10 +950,313 @@ fn main() -> Result<(), ExitFailure> {
                         format!("Failed to close the step {} of task {}", step_id, task_id)
                     })?;
                 }
+                StepCmd::Edit {
+                    task_id,
+                    step_id,
+                    descr,
+                } => {
+                    let text = descr_to_string(&descr);
+                    info!("Edit step {} of task {}", step_id, task_id);
+                    db::set_step_descr(&db_connection, task_id, step_id, &text).with_context(
+                        |_| format!("Failed to edit the step {} of task {}", step_id, task_id),
+                    )?;
+                }
+                StepCmd::Move {
+                    task_id,
+                    step_id,
+                    to,
+                } => {
+                    info!("Move step {} of task {} to {}", step_id, task_id, to);
+                    let new_step_id = db::move_step(&db_connection, task_id, step_id, to)
+                        .with_context(|_| {
+                            format!("Failed to move the step {} of task {}", step_id, task_id)
+                        })?;
+                    println!("Step moved, with id {}", new_step_id);
+                }
+                StepCmd::Insert {
+                    task_id,
+                    before,
+                    descr,
+                } => {
+                    let text = descr_to_string(&descr);
+                    info!("Insert a step to task {} before step {}", task_id, before);
+                    let new_step_id = db::insert_step(&db_connection, task_id, before, &text)
+                        .with_context(|_| format!("Failed to insert a step to task {}", task_id))?;
+                    println!("Create a new step, with id {}", new_step_id);
+                }
+                StepCmd::Renumber { task_id } => {
+                    info!("Renumber steps of task {}", task_id);
+                    db::renumber_steps(&db_connection, task_id).with_context(|_| {
+                        format!("Failed to renumber the steps of task {}", task_id)
+                    })?;
+                }
+            }
+        }
+        Cmd::Label(labelcmd) => {
+            let db_connection = db::get_db(&dbfile)?;
+            match labelcmd.cmd {
+                LabelCmd::Exclusive { remove, namespace } => {
+                    info!("Set namespace {} exclusive: {}", namespace, !remove);
+                    db::set_namespace_exclusive(&db_connection, &namespace, !remove).with_context(
+                        |_| format!("Failed to set the rule on namespace {}", namespace),
+                    )?;
+                }
+                LabelCmd::Rules => {
+                    for ns in db::get_exclusive_namespaces(&db_connection)? {
+                        println!("{}: exclusive", ns);
+                    }
+                }
             }
         }
+        Cmd::Sprint(sprintcmd) => {
+            let mut db_connection = db::get_db(&dbfile)?;
+            let tx = db_connection.transaction()?;
+            cmd_sprint(sprintcmd.cmd, &tx)?;
+            tx.commit()?;
+        }
         Cmd::Show(showopt) => {
-            cmd_show(showopt, &dbfile)?;
+            cmd_show(showopt, &dbfile, &config)?;
+        }
+        Cmd::Import(importopt) => match importopt.cmd {
+            ImportCmd::Trello {
+                import_opts,
+                lists,
+                archived,
+                file,
+            } => {
+                let input = std::fs::read_to_string(&file)
+                    .with_context(|_| format!("Failed to read {}", file.display()))?;
+                let board = trello::parse(&input)?;
+                let mut mapping = Vec::new();
+                for l in &lists {
+                    mapping.push(transfer::parse_mapping(l)?);
+                }
+                let statuses = trello::list_statuses(&board, &mapping, archived)?;
+                let tasks = trello::to_tasks(&board, &statuses, archived);
+                println!("Board {}", board.name);
+                for (id, status) in &statuses {
+                    if let Some(list) = board.lists.iter().find(|l| l.id == *id) {
+                        println!("  list {} -> {}", list.name, status);
+                    }
+                }
+                cmd_import(
+                    &dbfile,
+                    &tasks,
+                    &import_opts,
+                    &format!("Import {} tasks?", tasks.len()),
+                    &hooks,
+                    |db| transfer::create_all(db, &tasks),
+                )?;
+            }
+            ImportCmd::Json {
+                import_opts,
+                replace,
+                file,
+            } => {
+                let input = std::fs::read_to_string(&file)
+                    .with_context(|_| format!("Failed to read {}", file.display()))?;
+                let backup = backup::parse(&input)?;
+                let mode = if replace {
+                    backup::ImportMode::Replace
+                } else {
+                    backup::ImportMode::Merge
+                };
+                let tasks = backup::to_tasks(&backup, mode);
+                println!(
+                    "Backup of {}: {} tasks, {} sprints",
+                    backup.exported,
+                    backup.tasks.len(),
+                    backup.sprints.len()
+                );
+                let question = if replace {
+                    format!("Delete all the tasks and import {} tasks?", tasks.len())
+                } else {
+                    format!("Import {} tasks?", tasks.len())
+                };
+                cmd_import(&dbfile, &tasks, &import_opts, &question, &hooks, |db| {
+                    backup::import(db, &backup, mode)
+                })?;
+            }
+            ImportCmd::Todotxt { import_opts, file } => {
+                let input = std::fs::read_to_string(&file)
+                    .with_context(|_| format!("Failed to read {}", file.display()))?;
+                let tasks = todotxt::parse(&input);
+                cmd_import(
+                    &dbfile,
+                    &tasks,
+                    &import_opts,
+                    &format!("Import {} tasks?", tasks.len()),
+                    &hooks,
+                    |db| transfer::create_all(db, &tasks),
+                )?;
+            }
+            ImportCmd::Markdown { import_opts, file } => {
+                let input = std::fs::read_to_string(&file)
+                    .with_context(|_| format!("Failed to read {}", file.display()))?;
+                let tasks = markdown::parse(&input);
+                cmd_import(
+                    &dbfile,
+                    &tasks,
+                    &import_opts,
+                    &format!("Import {} tasks?", tasks.len()),
+                    &hooks,
+                    |db| transfer::create_all(db, &tasks),
+                )?;
+            }
+            ImportCmd::Csv {
+                import_opts,
+                map,
+                priority_values,
+                status_values,
+                label_delimiter,
+                delimiter,
+                file,
+            } => {
+                let input = std::fs::read_to_string(&file)
+                    .with_context(|_| format!("Failed to read {}", file.display()))?;
+                let mut mapping = csv::CsvMapping::default();
+                mapping.set_label_delimiter(&label_delimiter)?;
+                for m in &map {
+                    mapping.add_columns(m)?;
+                }
+                for p in &priority_values {
+                    mapping.add_priority(p)?;
+                }
+                for s in &status_values {
+                    mapping.add_status(s)?;
+                }
+                let records = csv::parse_records(&input, delimiter)?;
+                let (tasks, errors) = csv::to_tasks(&records, &mapping)?;
+                for (row, e) in &errors {
+                    error!("row {}: {}", row, e);
+                }
+                let question = if errors.is_empty() {
+                    format!("Import {} tasks?", tasks.len())
+                } else {
+                    format!(
+                        "Import {} tasks, skipping {} bad rows?",
+                        tasks.len(),
+                        errors.len()
+                    )
+                };
+                cmd_import(&dbfile, &tasks, &import_opts, &question, &hooks, |db| {
+                    transfer::create_all(db, &tasks)
+                })?;
+            }
+            ImportCmd::Taskwarrior { import_opts, file } => {
+                let input = std::fs::read_to_string(&file)
+                    .with_context(|_| format!("Failed to read {}", file.display()))?;
+                let tasks = taskwarrior::to_tasks(&taskwarrior::parse(&input)?);
+                cmd_import(
+                    &dbfile,
+                    &tasks,
+                    &import_opts,
+                    &format!("Import {} tasks?", tasks.len()),
+                    &hooks,
+                    |db| transfer::create_all(db, &tasks),
+                )?;
+            }
+        },
+        Cmd::Export(exportopt) => match exportopt.cmd {
+            ExportCmd::Json { output } => {
+                let db_connection = db::get_db(&dbfile)?;
+                let backup = backup::export(&db_connection)?;
+                let mut json = serde_json::to_string_pretty(&backup)?;
+                json.push('\n');
+                write_output(&output, &json)?;
+            }
+            ExportCmd::Todotxt {
+                output,
+                labels,
+                done,
+            } => {
+                let db_connection = db::get_db(&dbfile)?;
+                write_output(&output, &todotxt::export(&db_connection, &labels, done)?)?;
+            }
+            ExportCmd::Markdown {
+                output,
+                labels,
+                done,
+            } => {
+                let db_connection = db::get_db(&dbfile)?;
+                write_output(&output, &markdown::export(&db_connection, &labels, done)?)?;
+            }
+            ExportCmd::Taskwarrior {
+                output,
+                labels,
+                done,
+            } => {
+                let db_connection = db::get_db(&dbfile)?;
+                let mut json = taskwarrior::export(&db_connection, &labels, done)?;
+                json.push('\n');
+                write_output(&output, &json)?;
+            }
+            ExportCmd::Ical { output, labels } => {
+                let db_connection = db::get_db(&dbfile)?;
+                write_output(&output, &ical::export(&db_connection, &labels)?)?;
+            }
+        },
+        Cmd::GitSync(gitsyncopt) => {
+            let commits = gitsync::log(&gitsyncopt.repo, gitsyncopt.since.as_deref())?;
+            let mut db_connection = db::get_db(&dbfile)?;
+            let tx = db_connection.transaction()?;
+            let synced = gitsync::sync(&tx, &commits)?;
+            tx.commit()?;
+            for (commit, id, outcome) in synced {
+                match outcome {
+                    gitsync::Outcome::Completed => {
+                        println!(
+                            "Task {} completed by {} {}",
+                            id,
+                            commit.short_hash(),
+                            commit.subject()
+                        );
+                        // the commit is already there, only the on-done hook runs
+                        run_hook(&hooks, When::On, Event::Done, &db_connection, id)?;
+                    }
+                    gitsync::Outcome::Referenced => println!(
+                        "Task {} references {} {}",
+                        id,
+                        commit.short_hash(),
+                        commit.subject()
+                    ),
+                    gitsync::Outcome::AlreadySynced => {
+                        debug!("Commit {} already synced to task {}", commit.hash, id)
+                    }
+                    gitsync::Outcome::UnknownTask => warn!(
+                        "Commit {} mentions task {}, that doesn't exist",
+                        commit.short_hash(),
+                        id
+                    ),
+                }
+            }
+        }
+        Cmd::Changelog(changelogopt) => {
+            let db_connection = db::get_db(&dbfile)?;
+            let range = DateRange::from_local_days(Some(changelogopt.since), changelogopt.until);
+            let sections = report::changelog(&db_connection, &config.changelog, &range)?;
+            let date = changelogopt
+                .until
+                .unwrap_or_else(|| Local::today().naive_local());
+            print!(
+                "{}",
+                report::changelog_to_string(&sections, changelogopt.version.as_deref(), date)
+            );
+        }
+        Cmd::Standup(standupopt) => {
+            let db_connection = db::get_db(&dbfile)?;
+            let assignee =
+                mine_or_assignee(standupopt.mine, standupopt.assignee, config.current_user())?;
+            let standup = report::standup(
+                &db_connection,
+                Local::today().naive_local(),
+                &standupopt.labels,
+                assignee.as_deref(),
+            )?;
+            print!(
+                "{}",
+                report::standup_to_string(&standup, standupopt.markdown)
+            );
         }
     };
     trace!("myrello end");
@@ -1,7 +1,28 @@
 use exitfailure::ExitFailure;
+use failure::ResultExt;
+use log::{error, info, trace};
+use myrello::daemon;
+use myrello::MutConn;
+use std::io;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 use structopt::StructOpt;
 use structopt_flags::{ForceFlag, LogLevel, Verbose};
+use tokio::codec::{FramedRead, FramedWrite, LinesCodec};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::prelude::*;
+use tokio::runtime::Runtime;
+use tokio::timer::Interval;
+
+/// Set by SIGINT, SIGTERM and the Shutdown command
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+/// How often the shutdown flag is checked
+const TICK: Duration = Duration::from_millis(200);
+/// How long the open connections have to send their replies at shutdown
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
 
 #[derive(Debug, StructOpt)]
 struct Opt {
@@ -12,22 +33,149 @@ struct Opt {
     /// Specify the database file you want to use
     #[structopt(short = "d", long = "db", parse(from_os_str), raw(global = "true"))]
     dbfile: Option<PathBuf>,
+    /// The Unix domain socket to listen on, todod.sock next to the database if not specified
+    #[structopt(short = "s", long = "socket", parse(from_os_str))]
+    socket: Option<PathBuf>,
+    /// The pid file, todod.pid next to the database if not specified
+    #[structopt(short = "p", long = "pidfile", parse(from_os_str))]
+    pidfile: Option<PathBuf>,
 }
 
 struct Config {
     dbfile: PathBuf,
     force: bool,
+    socket: PathBuf,
+    pidfile: PathBuf,
 }
 
 impl From<Opt> for Config {
     fn from(opt: Opt) -> Self {
+        let dbfile = opt.dbfile.unwrap_or_else(myrello::db::dbfile_default);
         Config {
-            dbfile: opt.dbfile.unwrap_or_else(myrello::db::dbfile_default),
+            socket: opt
+                .socket
+                .unwrap_or_else(|| dbfile.with_file_name("todod.sock")),
+            pidfile: opt
+                .pidfile
+                .unwrap_or_else(|| dbfile.with_file_name("todod.pid")),
+            dbfile,
             force: opt.force_flag.force,
         }
     }
 }
 
+extern "C" fn on_signal(_: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+fn set_signal_handlers() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// What a connection waits for
+enum Input {
+    Request(String),
+    Tick,
+    Closed,
+}
+
+/// Serve the requests of a client, a line each, until it disconnects or the
+/// shutdown is requested; the replies already computed are flushed
+fn serve(stream: UnixStream, db: MutConn) -> impl Future<Item = (), Error = ()> {
+    let (reader, writer) = stream.split();
+    let requests = FramedRead::new(reader, LinesCodec::new())
+        .map(Input::Request)
+        .chain(stream::once(Ok(Input::Closed)));
+    let ticks = Interval::new_interval(TICK)
+        .map(|_| Input::Tick)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()));
+    let replies = requests
+        .select(ticks)
+        .take_while(|input| {
+            Ok(match input {
+                Input::Request(_) => true,
+                Input::Tick => !SHUTDOWN.load(Ordering::SeqCst),
+                Input::Closed => false,
+            })
+        })
+        .filter_map(|input| match input {
+            Input::Request(request) => Some(request),
+            _ => None,
+        })
+        .map(move |request| {
+            trace!("request {}", request);
+            let response = match db.lock() {
+                Ok(db) => daemon::execute(&db, &request),
+                Err(_) => daemon::Response {
+                    reply: "{\"error\":\"The database connection is poisoned\"}".to_string(),
+                    shutdown: true,
+                },
+            };
+            if response.shutdown {
+                info!("Shutdown requested");
+                SHUTDOWN.store(true, Ordering::SeqCst);
+            }
+            response.reply
+        });
+    FramedWrite::new(writer, LinesCodec::new())
+        .send_all(replies)
+        .map(|_| ())
+        .map_err(|e| error!("Connection error: {}", e))
+}
+
+/// Resolve when the shutdown is requested
+fn shutdown_requested() -> impl Future<Item = (), Error = ()> {
+    Interval::new_interval(TICK)
+        .map_err(|e| error!("Timer error: {}", e))
+        .skip_while(|_| Ok(!SHUTDOWN.load(Ordering::SeqCst)))
+        .into_future()
+        .map(|_| ())
+        .map_err(|_| ())
+}
+
+/// Listen on the socket until the shutdown
+fn listen(config: &Config, db: MutConn) -> Result<(), ExitFailure> {
+    if config.socket.exists() {
+        std::fs::remove_file(&config.socket)
+            .with_context(|_| format!("Failed to remove {}", config.socket.display()))?;
+    }
+    // only the owner of the database can send commands: the socket is created
+    // with mode 0600, no other thread is running yet
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(&config.socket);
+    unsafe { libc::umask(umask) };
+    let listener =
+        listener.with_context(|_| format!("Failed to listen on {}", config.socket.display()))?;
+    set_signal_handlers();
+    info!("Listening on {}", config.socket.display());
+    let server = listener
+        .incoming()
+        .map_err(|e| error!("Failed to accept a connection: {}", e))
+        .for_each(move |stream| {
+            tokio::spawn(serve(stream, db.clone()));
+            Ok(())
+        });
+    let mut runtime = Runtime::new()?;
+    // the listener is dropped with the server, no new connection is accepted
+    let _ = runtime.block_on(server.select(shutdown_requested()));
+    info!("Shutting down");
+    // the open connections end at the next tick, after sending their replies
+    let (tx, rx) = mpsc::channel();
+    let shutdown = runtime.shutdown_on_idle();
+    thread::spawn(move || {
+        let _ = shutdown.wait();
+        let _ = tx.send(());
+    });
+    if rx.recv_timeout(GRACE_PERIOD).is_err() {
+        error!("The open connections didn't end in time, they are dropped");
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), ExitFailure> {
     let opt = Opt::from_args();
     opt.verbose.set_log_level();
@@ -35,5 +183,23 @@ fn main() -> Result<(), ExitFailure> {
     if config.force {
         myrello::db::dbdir_create(&config.dbfile)?;
     }
-    Ok(())
+    if !config.dbfile.exists() {
+        return Err(failure::err_msg(format!(
+            "Database {} not found, create it with myrello database init",
+            config.dbfile.display()
+        ))
+        .into());
+    }
+    let db: MutConn = Arc::new(Mutex::new(myrello::db::get_db(&config.dbfile)?));
+    // a stale socket is replaced only if no other todod is running
+    daemon::write_pidfile(&config.pidfile)?;
+    let rv = listen(&config, db);
+    for path in &[&config.socket, &config.pidfile] {
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(path) {
+                error!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+    rv
 }
@@ -0,0 +1,447 @@
+//! Burndown and burnup charts
+//!
+//! The series are reconstructed from the creation and completion dates of the
+//! tasks, then rendered as an ASCII chart for the terminal or as an SVG image
+use crate::db;
+use crate::filter::Expr;
+use crate::metrics::local_day;
+use crate::sort::SortSpec;
+use crate::task::{self, ShowParams, Sprint};
+use chrono::prelude::*;
+use chrono::Duration;
+use rusqlite::{Connection, Error};
+use strum_macros::{Display, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+pub enum ChartKind {
+    /// The remaining work over time
+    #[strum(serialize = "burndown")]
+    Burndown,
+    /// The completed work and the total scope over time
+    #[strum(serialize = "burnup")]
+    Burnup,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+pub enum ChartStyle {
+    #[strum(serialize = "line")]
+    Line,
+    #[strum(serialize = "bar")]
+    Bar,
+}
+
+/// A task as seen by the charts: when it was created and completed (local
+/// days) and how much work it counts for
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartTask {
+    pub created: NaiveDate,
+    pub completed: Option<NaiveDate>,
+    pub points: u32,
+}
+
+/// The state of the work at the end of a day
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub day: NaiveDate,
+    /// The work of the tasks created so far
+    pub scope: u32,
+    /// The work of the tasks completed so far
+    pub completed: u32,
+}
+
+impl Point {
+    pub fn remaining(&self) -> u32 {
+        self.scope - self.completed
+    }
+}
+
+/// Reconstruct the series of the days from `first` to `last`, both included
+pub fn series(tasks: &[ChartTask], first: NaiveDate, last: NaiveDate) -> Vec<Point> {
+    let mut points = Vec::new();
+    let mut day = first;
+    while day <= last {
+        let scope = tasks.iter().filter(|t| t.created <= day);
+        let completed = scope
+            .clone()
+            .filter(|t| t.completed.map_or(false, |c| c <= day));
+        points.push(Point {
+            day,
+            scope: scope.map(|t| t.points).sum(),
+            completed: completed.map(|t| t.points).sum(),
+        });
+        day = day.succ();
+    }
+    points
+}
+
+fn chart_task(
+    creation_date: &str,
+    completion_date: Option<&str>,
+    storypoints: u32,
+    count_tasks: bool,
+) -> Option<ChartTask> {
+    Some(ChartTask {
+        created: local_day(creation_date)?,
+        completed: completion_date.and_then(local_day),
+        points: if count_tasks { 1 } else { storypoints },
+    })
+}
+
+/// The open and done tasks of the board, selected by the show parameters
+/// With `count_tasks` every task counts for one, instead of its story points
+pub fn board_tasks(
+    db: &Connection,
+    filter: Option<&Expr>,
+    param: &ShowParams,
+    count_tasks: bool,
+) -> Result<Vec<ChartTask>, Error> {
+    let mut rv = Vec::new();
+    for t in db::get_open_tasks_where(db, filter, &SortSpec::open_default())? {
+        let labels = db::get_labels(db, t.id)?;
+        if task::check_label(param.label, &labels)
+            && task::check_assignee(param.assignee, &t.assignee)
+        {
+            rv.extend(chart_task(
+                &t.creation_date,
+                None,
+                t.storypoints,
+                count_tasks,
+            ));
+        }
+    }
+    let done = db::get_done_tasks_where(
+        db,
+        filter,
+        &task::DateRange::default(),
+        &SortSpec::done_default(),
+    )?;
+    for t in done {
+        let labels = db::get_labels(db, t.id)?;
+        if task::check_label(param.label, &labels)
+            && task::check_assignee(param.assignee, &t.assignee)
+        {
+            rv.extend(chart_task(
+                &t.creation_date,
+                Some(&t.completion_date),
+                t.storypoints,
+                count_tasks,
+            ));
+        }
+    }
+    Ok(rv)
+}
+
+/// The tasks of a sprint, selected by the show parameters
+/// The tasks are in the scope since the beginning of the sprint
+pub fn sprint_tasks(
+    db: &Connection,
+    sprint: &Sprint,
+    filter: Option<&Expr>,
+    param: &ShowParams,
+    count_tasks: bool,
+) -> Result<Vec<ChartTask>, Error> {
+    let mut rv = Vec::new();
+    for (t, _) in db::get_sprint_tasks(db, sprint.id, &SortSpec::open_default())? {
+        let labels = db::get_labels(db, t.id)?;
+        if task::check_label(param.label, &labels)
+            && task::check_assignee(param.assignee, &t.assignee)
+            && filter.map_or(true, |f| f.eval(&t, &labels))
+        {
+            let completion_date = db::get_task_completion_date(db, t.id)?;
+            rv.extend(chart_task(
+                &t.creation_date,
+                completion_date.as_deref(),
+                t.storypoints,
+                count_tasks,
+            ));
+        }
+    }
+    Ok(rv)
+}
+
+/// The days of a sprint, until today if the sprint is not over
+pub fn sprint_days(sprint: &Sprint, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let start = NaiveDate::parse_from_str(&sprint.start_date, "%Y-%m-%d").ok()?;
+    let end = NaiveDate::parse_from_str(&sprint.end_date, "%Y-%m-%d").ok()?;
+    Some((start, end.min(today).max(start)))
+}
+
+/// The default days of a board chart: the last four weeks
+pub fn board_days(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    (today - Duration::weeks(4), today)
+}
+
+/// The terminal width, from $COLUMNS, 80 if unknown
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+/// The plotted series, with their symbol and name
+fn plotted(points: &[Point], kind: ChartKind) -> Vec<(char, &'static str, Vec<u32>)> {
+    match kind {
+        ChartKind::Burndown => vec![(
+            '*',
+            "remaining",
+            points.iter().map(Point::remaining).collect(),
+        )],
+        ChartKind::Burnup => vec![
+            ('o', "scope", points.iter().map(|p| p.scope).collect()),
+            (
+                '#',
+                "completed",
+                points.iter().map(|p| p.completed).collect(),
+            ),
+        ],
+    }
+}
+
+/// Render the chart as text, `width` characters wide and `height` rows high
+/// (axis and legend excluded)
+pub fn render_ascii(
+    points: &[Point],
+    kind: ChartKind,
+    style: ChartStyle,
+    width: usize,
+    height: usize,
+) -> String {
+    const MARGIN: usize = 8;
+    if points.is_empty() {
+        return "No data\n".to_string();
+    }
+    let height = height.max(2);
+    let plot_width = width.saturating_sub(MARGIN).max(10);
+    let series = plotted(points, kind);
+    let max = series
+        .iter()
+        .flat_map(|(_, _, values)| values.iter())
+        .cloned()
+        .max()
+        .unwrap_or(0)
+        .max(1) as usize;
+    let last = points.len() - 1;
+    // the chart is built by column, the row 0 is the bottom one
+    let columns: Vec<Vec<char>> = (0..plot_width)
+        .map(|col| {
+            let idx = (col * last + (plot_width - 1) / 2) / (plot_width - 1);
+            let mut column = vec![' '; height];
+            for (symbol, _, values) in &series {
+                let value = values[idx] as usize;
+                let row = (value * (height - 1) * 2 + max) / (2 * max);
+                match style {
+                    ChartStyle::Line => column[row] = *symbol,
+                    ChartStyle::Bar if value > 0 => {
+                        column[..=row].iter_mut().for_each(|c| *c = *symbol)
+                    }
+                    ChartStyle::Bar => {}
+                }
+            }
+            column
+        })
+        .collect();
+    let mut rv = String::new();
+    for r in 0..height {
+        let line: String = columns.iter().map(|c| c[height - 1 - r]).collect();
+        let label = if r == 0 {
+            max.to_string()
+        } else if r == height - 1 {
+            "0".to_string()
+        } else {
+            String::new()
+        };
+        rv.push_str(&format!("{:>6} |", label));
+        rv.push_str(line.trim_end());
+        rv.push('\n');
+    }
+    rv.push_str(&format!("{:>6} +{}\n", "", "-".repeat(plot_width)));
+    let first_day = points[0].day.to_string();
+    let last_day = points[last].day.to_string();
+    let gap = plot_width
+        .saturating_sub(first_day.len() + last_day.len())
+        .max(1);
+    rv.push_str(&format!(
+        "{:>6}  {}{}{}\n",
+        "",
+        first_day,
+        " ".repeat(gap),
+        if last > 0 { last_day } else { String::new() }
+    ));
+    let legend: Vec<String> = series
+        .iter()
+        .map(|(symbol, name, _)| format!("{} {}", symbol, name))
+        .collect();
+    rv.push_str(&format!("{:>6}  {}\n", "", legend.join("   ")));
+    rv
+}
+
+fn svg_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Render the chart as an SVG image
+pub fn render_svg(points: &[Point], kind: ChartKind, title: &str) -> String {
+    const WIDTH: f64 = 800.0;
+    const HEIGHT: f64 = 400.0;
+    const MARGIN: f64 = 50.0;
+    let series = plotted(points, kind);
+    let max = series
+        .iter()
+        .flat_map(|(_, _, values)| values.iter())
+        .cloned()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let last = points.len().saturating_sub(1).max(1) as f64;
+    let x = |i: usize| MARGIN + i as f64 * (WIDTH - 2.0 * MARGIN) / last;
+    let y = |v: u32| HEIGHT - MARGIN - f64::from(v) * (HEIGHT - 2.0 * MARGIN) / f64::from(max);
+    let colors = ["#d62728", "#1f77b4"];
+    let mut rv = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = WIDTH,
+        h = HEIGHT
+    );
+    rv.push_str(&format!(
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+        WIDTH, HEIGHT
+    ));
+    rv.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"16\" text-anchor=\"middle\">{}</text>\n",
+        WIDTH / 2.0,
+        MARGIN / 2.0,
+        svg_escape(title)
+    ));
+    rv.push_str(&format!(
+        "<polyline points=\"{m},{t} {m},{b} {r},{b}\" fill=\"none\" stroke=\"black\"/>\n",
+        m = MARGIN,
+        t = MARGIN,
+        b = HEIGHT - MARGIN,
+        r = WIDTH - MARGIN
+    ));
+    rv.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"12\" text-anchor=\"end\">{}</text>\n",
+        MARGIN - 5.0,
+        MARGIN + 4.0,
+        max
+    ));
+    rv.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"12\" text-anchor=\"end\">0</text>\n",
+        MARGIN - 5.0,
+        HEIGHT - MARGIN + 4.0
+    ));
+    if let (Some(first), Some(last_point)) = (points.first(), points.last()) {
+        rv.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"12\">{}</text>\n",
+            MARGIN,
+            HEIGHT - MARGIN + 18.0,
+            first.day
+        ));
+        rv.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"12\" text-anchor=\"end\">{}</text>\n",
+            WIDTH - MARGIN,
+            HEIGHT - MARGIN + 18.0,
+            last_point.day
+        ));
+    }
+    for (i, (_, name, values)) in series.iter().enumerate() {
+        let coords: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(j, v)| format!("{:.1},{:.1}", x(j), y(*v)))
+            .collect();
+        rv.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+            coords.join(" "),
+            colors[i % colors.len()]
+        ));
+        rv.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"12\" fill=\"{}\">{}</text>\n",
+            WIDTH - MARGIN - 100.0,
+            MARGIN + 16.0 * i as f64,
+            colors[i % colors.len()],
+            name
+        ));
+    }
+    rv.push_str("</svg>\n");
+    rv
+}
+
+#[cfg(test)]
+mod chart_tests {
+    use super::*;
+
+    fn d(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2019, 5, day)
+    }
+
+    fn sample() -> Vec<Point> {
+        let tasks = vec![
+            ChartTask {
+                created: d(1),
+                completed: Some(d(2)),
+                points: 3,
+            },
+            ChartTask {
+                created: d(1),
+                completed: None,
+                points: 2,
+            },
+            ChartTask {
+                created: d(3),
+                completed: Some(d(4)),
+                points: 5,
+            },
+        ];
+        series(&tasks, d(1), d(4))
+    }
+
+    #[test]
+    fn test_series() {
+        let uut = sample();
+        let values: Vec<(u32, u32)> = uut.iter().map(|p| (p.scope, p.completed)).collect();
+        assert_eq!(values, vec![(5, 0), (5, 3), (10, 3), (10, 8)]);
+        assert_eq!(uut[3].remaining(), 2);
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let uut = render_ascii(&sample(), ChartKind::Burndown, ChartStyle::Line, 18, 3);
+        assert_eq!(
+            uut,
+            "     7 |     ***\n       |*****   **\n     0 |\n       +----------\n        2019-05-01 2019-05-04\n        * remaining\n"
+        );
+        let uut = render_ascii(&sample(), ChartKind::Burnup, ChartStyle::Bar, 18, 3);
+        assert!(uut.starts_with("    10 |     ooo##\n"));
+        assert!(uut.ends_with("o scope   # completed\n"));
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let uut = render_svg(&sample(), ChartKind::Burnup, "board <all>");
+        assert!(uut.starts_with("<svg "));
+        assert!(uut.contains("board &lt;all&gt;"));
+        assert_eq!(uut.matches("<polyline").count(), 3);
+        assert!(uut.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_sprint_days() {
+        let sprint = Sprint {
+            id: 1,
+            name: "s1".to_string(),
+            start_date: "2019-05-01".to_string(),
+            end_date: "2019-05-14".to_string(),
+            capacity: 10,
+            closed_date: None,
+        };
+        assert_eq!(sprint_days(&sprint, d(6)), Some((d(1), d(6))));
+        assert_eq!(sprint_days(&sprint, d(20)), Some((d(1), d(14))));
+        assert_eq!(
+            sprint_days(&sprint, d(1) - Duration::days(3)),
+            Some((d(1), d(1)))
+        );
+    }
+}
@@ -1,5 +1,13 @@
-use crate::task::ShowParams;
+use crate::chart::{ChartKind, ChartStyle};
+use crate::config::ConfigError;
+use crate::filter::Expr;
+use crate::metrics::VelocityBy;
+use crate::output::Format;
+use crate::sort::SortSpec;
 use crate::task::TimeWindow;
+use crate::task::{Columns, ShowParams};
+use chrono::NaiveDate;
+use std::path::PathBuf;
 use structopt::StructOpt;
 use structopt_flags::ForceFlag;
 
@@ -17,6 +25,28 @@ pub enum Cmd {
     /// Work on tasks steps
     #[structopt(name = "step")]
     Step(StepOpt),
+    /// Work on labels and label namespaces
+    #[structopt(name = "label")]
+    Label(LabelOpt),
+    /// Work on sprints
+    #[structopt(name = "sprint")]
+    Sprint(SprintOpt),
+    /// Report for the standup meeting: done since the previous working day,
+    /// in progress and blocked tasks
+    #[structopt(name = "standup")]
+    Standup(StandupOpt),
+    /// Generate a Keep a Changelog section from the done tasks
+    #[structopt(name = "changelog")]
+    Changelog(ChangelogOpt),
+    /// Complete the tasks closed by the commits of a local git repository
+    #[structopt(name = "git-sync")]
+    GitSync(GitSyncOpt),
+    /// Import tasks from other tools or from a backup
+    #[structopt(name = "import")]
+    Import(ImportOpt),
+    /// Export tasks to other tools or to a backup
+    #[structopt(name = "export")]
+    Export(ExportOpt),
     /// Generate autocompletion for zsh
     #[structopt(name = "completion")]
     Completion,
@@ -80,10 +110,94 @@ pub enum ShowCmd {
     Done {
         #[structopt(flatten)]
         show_opts: ShowCommonOpt,
-        /// The time window desired
-        /// Possible values are: today, yesterday, week, month
+        /// The time window desired, in the local timezone (default: today)
+        /// Possible values are: today, yesterday, week, last-week, month, last-month,
+        /// quarter, last-quarter, year, last-year
         #[structopt(short = "T", long = "time")]
         time_window: Option<TimeWindow>,
+        /// Show the tasks completed since this date (YYYY-MM-DD)
+        #[structopt(long = "since", raw(conflicts_with = "\"time_window\""))]
+        since: Option<NaiveDate>,
+        /// Show the tasks completed until this date included (YYYY-MM-DD)
+        #[structopt(long = "until", raw(conflicts_with = "\"time_window\""))]
+        until: Option<NaiveDate>,
+    },
+    /// Show the tasks of a sprint, with committed and completed story points
+    #[structopt(name = "sprint")]
+    Sprint {
+        #[structopt(flatten)]
+        show_opts: ShowCommonOpt,
+        /// The sprint id, the active sprint if not specified
+        #[structopt(long = "sprint")]
+        sprint: Option<u32>,
+    },
+    /// Show the completed story points and tasks per week or per sprint
+    #[structopt(name = "velocity")]
+    Velocity {
+        #[structopt(flatten)]
+        show_opts: ShowCommonOpt,
+        /// Group the done tasks by week or by sprint
+        #[structopt(long = "by", default_value = "week")]
+        by: VelocityBy,
+        /// The number of weeks or sprints to show
+        #[structopt(short = "n", long = "number", default_value = "8")]
+        number: u32,
+        /// The number of weeks or sprints of the rolling average
+        #[structopt(long = "average", default_value = "3")]
+        average: usize,
+        /// Show the story points per label as well
+        #[structopt(long = "by-label")]
+        by_label: bool,
+    },
+    /// Show lead time and cycle time percentiles of the done tasks, and the slowest tasks
+    #[structopt(name = "flow")]
+    Flow {
+        #[structopt(flatten)]
+        show_opts: ShowCommonOpt,
+        /// The time window of the completion, in the local timezone (default: month)
+        /// Possible values are: today, yesterday, week, last-week, month, last-month,
+        /// quarter, last-quarter, year, last-year
+        #[structopt(short = "T", long = "time")]
+        time_window: Option<TimeWindow>,
+        /// Consider the tasks completed since this date (YYYY-MM-DD)
+        #[structopt(long = "since", raw(conflicts_with = "\"time_window\""))]
+        since: Option<NaiveDate>,
+        /// Consider the tasks completed until this date included (YYYY-MM-DD)
+        #[structopt(long = "until", raw(conflicts_with = "\"time_window\""))]
+        until: Option<NaiveDate>,
+        /// The number of slowest tasks to list
+        #[structopt(long = "slowest", default_value = "5")]
+        slowest: usize,
+    },
+    /// Show a burndown or burnup chart of the story points, of the board or of a sprint
+    #[structopt(name = "chart")]
+    Chart {
+        #[structopt(flatten)]
+        show_opts: ShowCommonOpt,
+        /// The kind of chart: burndown or burnup
+        #[structopt(long = "type", default_value = "burndown")]
+        kind: ChartKind,
+        /// The chart of a sprint, the active sprint if the id is not specified
+        #[structopt(long = "sprint")]
+        sprint: Option<Option<u32>>,
+        /// The first day of the chart of the board (default: four weeks ago)
+        #[structopt(long = "since")]
+        since: Option<NaiveDate>,
+        /// The style of the chart: line or bar
+        #[structopt(long = "style", default_value = "line")]
+        style: ChartStyle,
+        /// The width of the chart (default: the terminal width)
+        #[structopt(long = "width")]
+        width: Option<u16>,
+        /// The height of the chart, in rows
+        #[structopt(long = "height", default_value = "15")]
+        height: u16,
+        /// Count the tasks instead of the story points
+        #[structopt(long = "tasks")]
+        count_tasks: bool,
+        /// Print the chart as an SVG image, e.g. to redirect to a file
+        #[structopt(long = "svg")]
+        svg: bool,
     },
 }
 
@@ -93,6 +207,7 @@ pub struct ShowCommonOpt {
     #[structopt(short = "H", long = "hidden")]
     pub hidden: bool,
     /// Select one or more label as filter
+    /// Use namespace:* to select all the labels of a namespace
     #[structopt(short = "l", long = "label", raw(number_of_values = "1"))]
     pub labels: Vec<String>,
     /// Show references as well
@@ -101,6 +216,43 @@ pub struct ShowCommonOpt {
     /// Show steps as well
     #[structopt(short = "s", long = "steps")]
     pub steps: bool,
+    /// Show only the tasks assigned to the current user
+    #[structopt(short = "m", long = "mine")]
+    pub mine: bool,
+    /// Show only the tasks assigned to this user
+    #[structopt(short = "a", long = "assignee")]
+    pub assignee: Option<String>,
+    /// Show only the tasks matching the filter expression
+    /// e.g. 'priority>=high and (label:backend or label:infra) and not status:block and sp>3'
+    #[structopt(short = "w", long = "where")]
+    pub filter: Option<Expr>,
+    /// Sort the tasks, with a comma separated list of keys, each one optionally
+    /// followed by :asc or :desc
+    /// Keys are: priority, id, age, status, sp, due, label, completed
+    #[structopt(long = "sort")]
+    pub sort: Option<SortSpec>,
+    /// The output format
+    /// Possible values are: table, json, csv, markdown, tsv
+    #[structopt(short = "f", long = "format")]
+    pub format: Option<Format>,
+    /// The columns to show, as a comma separated list
+    /// Columns are: id, priority, status, labels, descr, sp, ref, due, assignee,
+    /// created, completed, age (in days) and steps (completed/total)
+    #[structopt(short = "C", long = "columns")]
+    pub columns: Option<Columns>,
+}
+
+/// The assignee to filter on: with --mine the current user, that must be known
+pub fn mine_or_assignee(
+    mine: bool,
+    assignee: Option<String>,
+    current_user: Option<String>,
+) -> Result<Option<String>, ConfigError> {
+    if mine {
+        current_user.ok_or(ConfigError::NoCurrentUser).map(Some)
+    } else {
+        Ok(assignee)
+    }
 }
 
 impl ShowCommonOpt {
@@ -111,11 +263,49 @@ impl ShowCommonOpt {
             reference: self.reference,
             storypoints: self.hidden,
             steps: self.steps,
+            assignee: self.assignee.as_deref(),
+            format: self.format.unwrap_or_default(),
+            columns: self.columns.as_ref().map(|c| c.0.as_slice()),
+        }
+    }
+    /// Without --columns, use the default columns of the view, if any
+    pub fn columns_or(&mut self, default: &Option<Columns>) {
+        if self.columns.is_none() {
+            self.columns = default.clone();
         }
     }
+    /// The sort order, or the default one if not specified
+    pub fn sort_or(&self, default: SortSpec) -> SortSpec {
+        self.sort.clone().unwrap_or(default)
+    }
+    /// With --mine, filter on the current user
+    pub fn resolve_mine(&mut self, current_user: Option<String>) -> Result<(), ConfigError> {
+        self.assignee = mine_or_assignee(self.mine, self.assignee.take(), current_user)?;
+        Ok(())
+    }
     pub fn merge(&mut self, to_merge: &ShowCommonOpt) {
         self.hidden |= to_merge.hidden;
         self.reference |= to_merge.reference;
+        self.steps |= to_merge.steps;
+        self.mine |= to_merge.mine;
+        if to_merge.assignee.is_some() {
+            self.assignee = to_merge.assignee.clone();
+        }
+        if to_merge.sort.is_some() {
+            self.sort = to_merge.sort.clone();
+        }
+        if to_merge.format.is_some() {
+            self.format = to_merge.format;
+        }
+        if to_merge.columns.is_some() {
+            self.columns = to_merge.columns.clone();
+        }
+        if let Some(to_merge_filter) = &to_merge.filter {
+            self.filter = match self.filter.take() {
+                Some(filter) => Some(filter.and(to_merge_filter.clone())),
+                None => Some(to_merge_filter.clone()),
+            };
+        }
         to_merge
             .labels
             .iter()
@@ -146,6 +336,9 @@ pub enum TaskCmd {
         /// the story points
         #[structopt(short = "S", long = "story-points")]
         storypoint: Option<u32>,
+        /// the due date (YYYY-MM-DD)
+        #[structopt(long = "due")]
+        due: Option<NaiveDate>,
         /// The task description
         #[structopt(raw(required = "true"))]
         descr: Vec<String>,
@@ -178,6 +371,9 @@ pub enum TaskCmd {
         /// set a reference to the task
         #[structopt(short = "r", long = "reference")]
         reference: Option<String>,
+        /// the due date (YYYY-MM-DD)
+        #[structopt(long = "due")]
+        due: Option<NaiveDate>,
         /// The task description
         #[structopt()]
         descr: Vec<String>,
@@ -197,6 +393,28 @@ pub enum TaskCmd {
     /// Increase the priority of a task
     #[structopt(name = "prio")]
     Prio(OptTaskOnly),
+    /// Assign a task to a user, the current user if not specified
+    #[structopt(name = "assign")]
+    Assign {
+        /// The task id
+        #[structopt(short = "t", long = "task")]
+        task: u32,
+        /// The assignee
+        assignee: Option<String>,
+    },
+    /// Remove the assignee of a task
+    #[structopt(name = "unassign")]
+    Unassign(OptTaskOnly),
+    /// Set the note of a task, a longer description; without text, the note is removed
+    #[structopt(name = "note")]
+    Note {
+        /// The task id
+        #[structopt(short = "t", long = "task")]
+        task: u32,
+        /// The note text
+        #[structopt()]
+        note: Vec<String>,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -234,6 +452,16 @@ pub enum StepCmd {
         #[structopt(short = "s", long = "step")]
         step_id: u32,
     },
+    /// Reopen a completed step, reopening the task as well if it's done
+    #[structopt(name = "reopen")]
+    Reopen {
+        /// The working task
+        #[structopt(short = "t", long = "task")]
+        task_id: u32,
+        /// The step id
+        #[structopt(short = "s", long = "step")]
+        step_id: u32,
+    },
     /// Delete a step
     #[structopt(name = "delete")]
     Delete {
@@ -244,11 +472,349 @@ pub enum StepCmd {
         #[structopt(short = "s", long = "step")]
         step_id: u32,
     },
+    /// Set a new description for the step
+    #[structopt(name = "edit")]
+    Edit {
+        /// The working task
+        #[structopt(short = "t", long = "task")]
+        task_id: u32,
+        /// The step id
+        #[structopt(short = "s", long = "step")]
+        step_id: u32,
+        /// The step description
+        #[structopt(raw(required = "true"))]
+        descr: Vec<String>,
+    },
+    /// Move a step to another position
+    #[structopt(name = "move")]
+    Move {
+        /// The working task
+        #[structopt(short = "t", long = "task")]
+        task_id: u32,
+        /// The step id
+        #[structopt(short = "s", long = "step")]
+        step_id: u32,
+        /// The new position of the step
+        #[structopt(long = "to")]
+        to: u32,
+    },
+    /// Insert a new step before an existing one
+    #[structopt(name = "insert")]
+    Insert {
+        /// The working task
+        #[structopt(short = "t", long = "task")]
+        task_id: u32,
+        /// The step id the new step is inserted before
+        #[structopt(short = "b", long = "before")]
+        before: u32,
+        /// The step description
+        #[structopt(raw(required = "true"))]
+        descr: Vec<String>,
+    },
+    /// Renumber the steps of a task, removing the gaps
+    #[structopt(name = "renumber")]
+    Renumber {
+        /// The working task
+        #[structopt(short = "t", long = "task")]
+        task_id: u32,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct LabelOpt {
+    #[structopt(subcommand)]
+    pub cmd: LabelCmd,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum LabelCmd {
+    /// Allow only one label per task in the namespace (e.g. size:S or size:L)
+    #[structopt(name = "exclusive")]
+    Exclusive {
+        /// Remove the rule, multiple labels are allowed again
+        #[structopt(short = "r", long = "remove")]
+        remove: bool,
+        /// The label namespace
+        namespace: String,
+    },
+    /// Show the exclusive namespaces
+    #[structopt(name = "rules")]
+    Rules,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct StandupOpt {
+    /// Select one or more label as filter
+    /// Use namespace:* to select all the labels of a namespace
+    #[structopt(short = "l", long = "label", raw(number_of_values = "1"))]
+    pub labels: Vec<String>,
+    /// Show only the tasks assigned to the current user
+    #[structopt(short = "m", long = "mine")]
+    pub mine: bool,
+    /// Show only the tasks assigned to this user
+    #[structopt(short = "a", long = "assignee")]
+    pub assignee: Option<String>,
+    /// Print the report as markdown, instead of plain text
+    #[structopt(long = "markdown")]
+    pub markdown: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ImportOpt {
+    #[structopt(subcommand)]
+    pub cmd: ImportCmd,
+}
+
+/// The options shared by all the imports
+#[derive(Debug, StructOpt)]
+pub struct ImportCommonOpt {
+    /// Only show the tasks that would be imported
+    #[structopt(short = "n", long = "dry-run")]
+    pub dry_run: bool,
+    /// Import without asking for confirmation
+    #[structopt(short = "y", long = "yes")]
+    pub yes: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ImportCmd {
+    /// Import a Trello board, from its JSON export
+    #[structopt(name = "trello")]
+    Trello {
+        #[structopt(flatten)]
+        import_opts: ImportCommonOpt,
+        /// The status of a Trello list, as NAME=STATUS (e.g. "Code review=in_progress")
+        #[structopt(short = "L", long = "list", raw(number_of_values = "1"))]
+        lists: Vec<String>,
+        /// Import archived cards and lists as well
+        #[structopt(long = "archived")]
+        archived: bool,
+        /// The JSON export of the board
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Import a backup, created with export json
+    #[structopt(name = "json")]
+    Json {
+        #[structopt(flatten)]
+        import_opts: ImportCommonOpt,
+        /// Delete all the existing tasks and keep the ids of the backup,
+        /// instead of adding the tasks with new ids
+        #[structopt(long = "replace")]
+        replace: bool,
+        /// The backup file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Import a todo.txt file
+    #[structopt(name = "todotxt")]
+    Todotxt {
+        #[structopt(flatten)]
+        import_opts: ImportCommonOpt,
+        /// The todo.txt file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Import the checklists of a markdown document: the items are tasks,
+    /// the nested items are steps, the headings are labels
+    #[structopt(name = "markdown")]
+    Markdown {
+        #[structopt(flatten)]
+        import_opts: ImportCommonOpt,
+        /// The markdown file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Import the rows of a CSV file, the first row is the header
+    #[structopt(name = "csv")]
+    Csv {
+        #[structopt(flatten)]
+        import_opts: ImportCommonOpt,
+        /// The column of a task field, as FIELD=COLUMN, comma separated
+        /// (e.g. "descr=Title,sp=Points"); the fields are descr, priority, status, labels,
+        /// sp, ref, note, assignee and due, a field not mapped is read from the column
+        /// with its name
+        #[structopt(short = "m", long = "map", raw(number_of_values = "1"))]
+        map: Vec<String>,
+        /// The priority of a value of the priority column, as VALUE=PRIORITY (e.g. "P1=urgent")
+        #[structopt(long = "priority-value", raw(number_of_values = "1"))]
+        priority_values: Vec<String>,
+        /// The status of a value of the status column, as VALUE=STATUS (e.g. "Doing=in_progress")
+        #[structopt(long = "status-value", raw(number_of_values = "1"))]
+        status_values: Vec<String>,
+        /// The separator of the labels in the labels column
+        #[structopt(long = "label-delimiter", default_value = ",")]
+        label_delimiter: String,
+        /// The separator of the fields
+        #[structopt(long = "delimiter", default_value = ",")]
+        delimiter: char,
+        /// The CSV file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Import a Taskwarrior JSON export
+    #[structopt(name = "taskwarrior")]
+    Taskwarrior {
+        #[structopt(flatten)]
+        import_opts: ImportCommonOpt,
+        /// The output of task export
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportOpt {
+    #[structopt(subcommand)]
+    pub cmd: ExportCmd,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ExportCmd {
+    /// Export the whole database as a versioned JSON document
+    #[structopt(name = "json")]
+    Json {
+        /// The output file, the standard output if not specified
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Export the tasks in the todo.txt format
+    #[structopt(name = "todotxt")]
+    Todotxt {
+        /// The output file, the standard output if not specified
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Export only the tasks with these labels
+        #[structopt(short = "l", long = "label", raw(number_of_values = "1"))]
+        labels: Vec<String>,
+        /// Export the done tasks as well
+        #[structopt(long = "done")]
+        done: bool,
+    },
+    /// Export the tasks as markdown checklists, under the headings of their labels
+    #[structopt(name = "markdown")]
+    Markdown {
+        /// The output file, the standard output if not specified
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Export only the tasks with these labels
+        #[structopt(short = "l", long = "label", raw(number_of_values = "1"))]
+        labels: Vec<String>,
+        /// Export the done tasks as well
+        #[structopt(long = "done")]
+        done: bool,
+    },
+    /// Export the tasks in the Taskwarrior JSON format, to be imported with task import
+    #[structopt(name = "taskwarrior")]
+    Taskwarrior {
+        /// The output file, the standard output if not specified
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Export only the tasks with these labels
+        #[structopt(short = "l", long = "label", raw(number_of_values = "1"))]
+        labels: Vec<String>,
+        /// Export the done tasks as well
+        #[structopt(long = "done")]
+        done: bool,
+    },
+    /// Export the open and done tasks as an iCalendar file of VTODO components
+    #[structopt(name = "ical")]
+    Ical {
+        /// The output file, the standard output if not specified
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// Export only the tasks with these labels
+        #[structopt(short = "l", long = "label", raw(number_of_values = "1"))]
+        labels: Vec<String>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ChangelogOpt {
+    /// The tasks completed since this date (YYYY-MM-DD)
+    #[structopt(long = "since")]
+    pub since: NaiveDate,
+    /// The tasks completed until this date included (YYYY-MM-DD)
+    #[structopt(long = "until")]
+    pub until: Option<NaiveDate>,
+    /// The version of the release, Unreleased if not specified
+    #[structopt(long = "version")]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct GitSyncOpt {
+    /// The git repository
+    #[structopt(long = "repo", default_value = ".", parse(from_os_str))]
+    pub repo: PathBuf,
+    /// Only the commits after this one (e.g. HEAD~1 in a post-commit hook)
+    #[structopt(long = "since")]
+    pub since: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SprintOpt {
+    #[structopt(subcommand)]
+    pub cmd: SprintCmd,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SprintCmd {
+    /// Create a new sprint
+    #[structopt(name = "new")]
+    New {
+        /// The first day of the sprint (YYYY-MM-DD), today if not specified
+        #[structopt(long = "start")]
+        start: Option<NaiveDate>,
+        /// The last day of the sprint (YYYY-MM-DD)
+        #[structopt(long = "end")]
+        end: NaiveDate,
+        /// The story points capacity of the sprint
+        #[structopt(long = "capacity")]
+        capacity: u32,
+        /// The sprint name
+        #[structopt(raw(required = "true"))]
+        name: Vec<String>,
+    },
+    /// Add a task to a sprint
+    #[structopt(name = "add")]
+    Add {
+        /// The sprint id, the active sprint if not specified
+        #[structopt(long = "sprint")]
+        sprint: Option<u32>,
+        /// The task id
+        #[structopt(short = "t", long = "task")]
+        task: u32,
+    },
+    /// Remove a task from a sprint
+    #[structopt(name = "remove")]
+    Remove {
+        /// The sprint id, the active sprint if not specified
+        #[structopt(long = "sprint")]
+        sprint: Option<u32>,
+        /// The task id
+        #[structopt(short = "t", long = "task")]
+        task: u32,
+    },
+    /// Close a sprint, unfinished tasks are moved to the next sprint
+    #[structopt(name = "complete")]
+    Complete {
+        /// The sprint id, the active sprint if not specified
+        #[structopt(long = "sprint")]
+        sprint: Option<u32>,
+        /// The sprint receiving the unfinished tasks, by default the next active one
+        #[structopt(long = "next")]
+        next: Option<u32>,
+    },
+    /// Show all sprints
+    #[structopt(name = "list")]
+    List,
 }
 
 #[cfg(test)]
 mod cli_opt_tests {
     use super::*;
+    use crate::task::Column;
 
     #[test]
     fn test_showcommonopt_default() {
@@ -271,6 +837,16 @@ mod cli_opt_tests {
         assert!(uut.labels.is_empty());
     }
     #[test]
+    fn test_showcommonopt_steps() {
+        // show all -s: the option is parsed by the subcommand
+        let mut uut = ShowOpt::from_iter(&["show", "all", "-s"]);
+        match uut.cmd.take() {
+            Some(ShowCmd::All { show_opts }) => uut.show_opts.merge(&show_opts),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(uut.show_opts.steps);
+    }
+    #[test]
     fn test_showcommonopt_labels() {
         let mut label_uut = Vec::new();
         label_uut.push("label1".to_string());
@@ -284,6 +860,72 @@ mod cli_opt_tests {
         assert_eq!(uut.labels.len(), 1);
         assert_eq!(uut.labels.pop().unwrap(), "label1".to_string());
     }
+    #[test]
+    fn test_showcommonopt_assignee() {
+        let mut uut = ShowCommonOpt::default();
+        uut.merge(&ShowCommonOpt {
+            mine: true,
+            assignee: Some("alice".to_string()),
+            ..ShowCommonOpt::default()
+        });
+        assert!(uut.mine);
+        assert_eq!(uut.as_show_params("").assignee, Some("alice"));
+        uut.merge(&ShowCommonOpt::default());
+        assert_eq!(uut.assignee, Some("alice".to_string()));
+    }
+    #[test]
+    fn test_showcommonopt_mine() {
+        let mut uut = ShowCommonOpt {
+            mine: true,
+            ..ShowCommonOpt::default()
+        };
+        assert!(uut.resolve_mine(None).is_err());
+        uut.resolve_mine(Some("bob".to_string())).unwrap();
+        assert_eq!(uut.assignee, Some("bob".to_string()));
+        let mut uut = ShowCommonOpt {
+            assignee: Some("alice".to_string()),
+            ..ShowCommonOpt::default()
+        };
+        uut.resolve_mine(None).unwrap();
+        assert_eq!(uut.assignee, Some("alice".to_string()));
+    }
+    #[test]
+    fn test_showcommonopt_filter() {
+        let mut uut = ShowCommonOpt {
+            filter: Some("sp>3".parse().unwrap()),
+            ..ShowCommonOpt::default()
+        };
+        uut.merge(&ShowCommonOpt {
+            filter: Some("label:infra".parse().unwrap()),
+            ..ShowCommonOpt::default()
+        });
+        assert_eq!(uut.filter, Some("sp>3 and label:infra".parse().unwrap()));
+    }
+    #[test]
+    fn test_showcommonopt_format() {
+        let mut uut = ShowCommonOpt::default();
+        assert_eq!(uut.as_show_params("").format, Format::Table);
+        uut.merge(&ShowCommonOpt {
+            format: Some(Format::Json),
+            ..ShowCommonOpt::default()
+        });
+        uut.merge(&ShowCommonOpt::default());
+        assert_eq!(uut.as_show_params("").format, Format::Json);
+    }
+    #[test]
+    fn test_showcommonopt_columns() {
+        let mut uut = ShowCommonOpt::default();
+        assert_eq!(uut.as_show_params("").columns, None);
+        uut.merge(&ShowCommonOpt {
+            columns: Some("id,age".parse().unwrap()),
+            ..ShowCommonOpt::default()
+        });
+        uut.columns_or(&Some("id,descr".parse().unwrap()));
+        assert_eq!(
+            uut.as_show_params("").columns,
+            Some(&[Column::Id, Column::Age][..])
+        );
+    }
 
     // currently a feature too hard to implement at this level
     //    #[test]
@@ -0,0 +1,187 @@
+use crate::task::Columns;
+use failure::Fail;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Fail, Debug)]
+pub enum ConfigError {
+    #[fail(display = "Failed to read the configuration file {}", _0)]
+    Read(String),
+    #[fail(display = "Failed to parse the configuration file {}: {}", _0, _1)]
+    Parse(String, String),
+    #[fail(display = "The current user is unknown: set user in the configuration file or $USER")]
+    NoCurrentUser,
+}
+
+/// What to do when the last step of a task is completed
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompleteTask {
+    /// The task is completed automatically
+    Auto,
+    /// The user is asked whether to complete the task
+    Prompt,
+    /// Nothing happens, the task stays open
+    Never,
+}
+
+impl Default for CompleteTask {
+    fn default() -> Self {
+        CompleteTask::Prompt
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct StepsConfig {
+    pub complete_task: CompleteTask,
+}
+
+/// The default columns of each view, the built-in ones if not set
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ColumnsConfig {
+    pub all: Option<Columns>,
+    pub short: Option<Columns>,
+    pub backlog: Option<Columns>,
+    pub work: Option<Columns>,
+    pub done: Option<Columns>,
+    pub sprint: Option<Columns>,
+}
+
+/// How the changelog is generated from the done tasks
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ChangelogConfig {
+    /// The section of the tasks with a label, e.g. feature = "Added"
+    /// A label filter ending with `:*` selects a whole namespace
+    pub sections: BTreeMap<String, String>,
+    /// The section of the tasks without a mapped label, left out if not set
+    pub default_section: Option<String>,
+}
+
+impl Default for ChangelogConfig {
+    fn default() -> Self {
+        let sections = [
+            ("feature", "Added"),
+            ("enhancement", "Changed"),
+            ("change", "Changed"),
+            ("deprecated", "Deprecated"),
+            ("removed", "Removed"),
+            ("bug", "Fix"),
+            ("fix", "Fix"),
+            ("security", "Security"),
+        ];
+        ChangelogConfig {
+            sections: sections
+                .iter()
+                .map(|(l, s)| (l.to_string(), s.to_string()))
+                .collect(),
+            default_section: None,
+        }
+    }
+}
+
+/// The user scripts run on the task events
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// The directory of the hooks, the hooks directory next to the
+    /// configuration file if not set
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The identity of the current user, $USER if not set
+    pub user: Option<String>,
+    pub steps: StepsConfig,
+    pub columns: ColumnsConfig,
+    pub changelog: ChangelogConfig,
+    pub hooks: HooksConfig,
+}
+
+impl Config {
+    pub fn current_user(&self) -> Option<String> {
+        self.user.clone().or_else(|| std::env::var("USER").ok())
+    }
+}
+
+pub fn config_default() -> PathBuf {
+    let default_dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("./"));
+    default_dir.join("myrello").join("config.toml")
+}
+
+/// Load the configuration file; a missing file means default configuration
+pub fn load(filename: &Path) -> Result<Config, ConfigError> {
+    if !filename.exists() {
+        return Ok(Config::default());
+    }
+    let name = filename.to_string_lossy().to_string();
+    let content = std::fs::read_to_string(filename).map_err(|_| ConfigError::Read(name.clone()))?;
+    toml::from_str(&content).map_err(|e| ConfigError::Parse(name, e.to_string()))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_config_empty() {
+        let uut: Config = toml::from_str("").unwrap();
+        assert_eq!(uut.steps.complete_task, CompleteTask::Prompt);
+    }
+
+    #[test]
+    fn test_config_steps() {
+        let uut: Config = toml::from_str("[steps]\ncomplete_task = \"auto\"\n").unwrap();
+        assert_eq!(uut.steps.complete_task, CompleteTask::Auto);
+        assert!(toml::from_str::<Config>("[steps]\ncomplete_task = \"maybe\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_columns() {
+        let uut: Config = toml::from_str("[columns]\nwork = \"id,descr,steps\"\n").unwrap();
+        assert_eq!(uut.columns.work, Some("id,descr,steps".parse().unwrap()));
+        assert_eq!(uut.columns.all, None);
+        assert!(toml::from_str::<Config>("[columns]\nall = \"id,size\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_changelog() {
+        let uut: Config = toml::from_str("").unwrap();
+        assert_eq!(
+            uut.changelog.sections.get("bug").map(String::as_str),
+            Some("Fix")
+        );
+        let uut: Config = toml::from_str(
+            "[changelog]\ndefault_section = \"Changed\"\n\
+             [changelog.sections]\n\"type:feature\" = \"Added\"\n",
+        )
+        .unwrap();
+        assert_eq!(uut.changelog.sections.len(), 1);
+        assert_eq!(uut.changelog.default_section, Some("Changed".to_string()));
+    }
+
+    #[test]
+    fn test_config_hooks() {
+        let uut: Config = toml::from_str("").unwrap();
+        assert_eq!(uut.hooks.dir, None);
+        let uut: Config = toml::from_str("[hooks]\ndir = \"/etc/myrello/hooks\"\n").unwrap();
+        assert_eq!(uut.hooks.dir, Some(PathBuf::from("/etc/myrello/hooks")));
+    }
+
+    #[test]
+    fn test_config_user() {
+        let uut: Config = toml::from_str("user = \"alice\"\n").unwrap();
+        assert_eq!(uut.current_user(), Some("alice".to_string()));
+    }
+
+    #[test]
+    fn test_config_missing_file() {
+        let uut = load(Path::new("/nonexistent/myrello/config.toml")).unwrap();
+        assert_eq!(uut.steps.complete_task, CompleteTask::Prompt);
+    }
+}
@@ -0,0 +1,223 @@
+//! The commands served by todod
+//!
+//! A request is a JSON command of the `op` module on a single line, the
+//! `name` field selects the command; the reply is a JSON reply on a single
+//! line, an `ErrorReply` if the command failed
+use crate::op::{self, JsonCommand};
+use crate::task;
+use failure::Fail;
+use rusqlite::{Connection, Error};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// How many times a stale pid file is replaced before giving up
+const PIDFILE_ATTEMPTS: u32 = 3;
+
+#[derive(Fail, Debug)]
+pub enum DaemonError {
+    #[fail(display = "Failed to access the pid file {}: {}", _0, _1)]
+    PidFile(String, #[cause] std::io::Error),
+    #[fail(display = "todod is already running, with pid {}", _0)]
+    AlreadyRunning(i32),
+}
+
+/// The reply to a request, and whether the daemon has to stop
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub reply: String,
+    pub shutdown: bool,
+}
+
+fn reply<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|e| error_reply(&e.to_string()))
+}
+
+fn error_reply(error: &str) -> String {
+    // the error reply is always serializable
+    serde_json::to_string(&op::ErrorReply {
+        error: error.to_string(),
+    })
+    .unwrap_or_default()
+}
+
+fn show_one_task(db: &Connection, request: &str) -> String {
+    let command: op::ShowOneTask = match serde_json::from_str(request) {
+        Ok(command) => command,
+        Err(e) => return error_reply(&format!("Invalid command: {}", e)),
+    };
+    match task::get_op_task(db, command.id) {
+        Ok(result) => reply(&op::ShowOneTaskReply { result }),
+        Err(Error::QueryReturnedNoRows) => error_reply(&format!("Task {} not found", command.id)),
+        Err(e) => error_reply(&format!("Database error: {}", e)),
+    }
+}
+
+/// Execute a request against the database
+pub fn execute(db: &Connection, request: &str) -> Response {
+    let name = match serde_json::from_str::<op::CommandName>(request) {
+        Ok(command) => command.name,
+        Err(e) => {
+            return Response {
+                reply: error_reply(&format!("Invalid command: {}", e)),
+                shutdown: false,
+            }
+        }
+    };
+    match name.as_str() {
+        "ShowOneTask" => Response {
+            reply: show_one_task(db, request),
+            shutdown: false,
+        },
+        "Shutdown" => Response {
+            reply: reply(&op::ShutdownReply {
+                result: "ok".to_string(),
+            }),
+            shutdown: true,
+        },
+        _ => Response {
+            reply: error_reply(&format!("Unknown command {}", name)),
+            shutdown: false,
+        },
+    }
+}
+
+/// A command as a request line
+pub fn request<T: JsonCommand>(command: &T) -> Result<String, serde_json::Error> {
+    Ok(String::from_utf8_lossy(&command.as_bytes()?).to_string())
+}
+
+fn is_running(pid: i32) -> bool {
+    // signal 0 only checks that the process exists, a process of another
+    // user exists as well
+    pid > 0
+        && (unsafe { libc::kill(pid, 0) } == 0
+            || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+/// The pid written in a pid file, None if the file is gone or has no pid
+/// A todod starting at the same time may have created the file without
+/// writing its pid yet, so an empty file is read again once
+fn read_pidfile(pidfile: &Path) -> Option<i32> {
+    for _ in 0..2 {
+        let content = std::fs::read_to_string(pidfile).ok()?;
+        if let Ok(pid) = content.trim().parse::<i32>() {
+            return Some(pid);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    None
+}
+
+/// Write the pid of this process in the pid file
+/// The pid file is created only if it doesn't exist, so two todod started
+/// together cannot both pass the check; a pid file of a process still running
+/// means that todod is already running, the pid file of a dead process is
+/// removed and created again
+pub fn write_pidfile(pidfile: &Path) -> Result<(), DaemonError> {
+    let name = pidfile.display().to_string();
+    let pid = std::process::id() as i32;
+    for _ in 0..PIDFILE_ATTEMPTS {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(pidfile)
+        {
+            Ok(mut file) => {
+                return writeln!(file, "{}", pid).map_err(|e| DaemonError::PidFile(name, e))
+            }
+            Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {
+                if let Some(running) = read_pidfile(pidfile) {
+                    if running != pid && is_running(running) {
+                        return Err(DaemonError::AlreadyRunning(running));
+                    }
+                }
+                if let Err(e) = std::fs::remove_file(pidfile) {
+                    if e.kind() != ErrorKind::NotFound {
+                        return Err(DaemonError::PidFile(name, e));
+                    }
+                }
+            }
+            Err(e) => return Err(DaemonError::PidFile(name, e)),
+        }
+    }
+    Err(DaemonError::PidFile(
+        name,
+        std::io::Error::new(
+            ErrorKind::AlreadyExists,
+            "the pid file keeps being recreated",
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod daemon_tests {
+    use super::*;
+    use crate::db;
+    use crate::op::JsonReply;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_execute() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let db = db::get_db(dbfile.path()).unwrap();
+        let id = db::add_task(&db, "a task").unwrap();
+        db::add_step(&db, id, "start").unwrap();
+        let command = op::ShowOneTask {
+            name: "ShowOneTask".to_string(),
+            id,
+        };
+        let uut = execute(&db, &request(&command).unwrap());
+        assert!(!uut.shutdown);
+        let reply = op::ShowOneTaskReply::from_bytes(uut.reply.as_bytes()).unwrap();
+        assert_eq!(reply.result.descr, "a task");
+        assert_eq!(reply.result.status, "todo");
+        let command = op::ShowOneTask {
+            name: "ShowOneTask".to_string(),
+            id: 42,
+        };
+        let uut = execute(&db, &request(&command).unwrap());
+        let reply = op::ErrorReply::from_bytes(uut.reply.as_bytes()).unwrap();
+        assert_eq!(reply.error, "Task 42 not found");
+        let uut = execute(&db, "{\"name\":\"Format\"}");
+        let reply = op::ErrorReply::from_bytes(uut.reply.as_bytes()).unwrap();
+        assert_eq!(reply.error, "Unknown command Format");
+        assert!(op::ErrorReply::from_bytes(execute(&db, "not json").reply.as_bytes()).is_ok());
+        let command = op::Shutdown {
+            name: "Shutdown".to_string(),
+        };
+        let uut = execute(&db, &request(&command).unwrap());
+        assert!(uut.shutdown);
+        assert!(op::ShutdownReply::from_bytes(uut.reply.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_write_pidfile() {
+        let temp = TempDir::new().unwrap();
+        let pidfile = temp.child("todod.pid");
+        write_pidfile(pidfile.path()).unwrap();
+        let pid = std::fs::read_to_string(pidfile.path()).unwrap();
+        assert_eq!(pid.trim(), std::process::id().to_string());
+        // this process, e.g. after a restart with the same pid
+        write_pidfile(pidfile.path()).unwrap();
+        // pid 1 is always running
+        pidfile.write_str("1\n").unwrap();
+        match write_pidfile(pidfile.path()) {
+            Err(DaemonError::AlreadyRunning(pid)) => assert_eq!(pid, 1),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(pidfile.path()).unwrap(), "1\n");
+        // the pid file of a dead process, or without a pid, is replaced
+        for stale in &["2147483647\n", ""] {
+            pidfile.write_str(stale).unwrap();
+            write_pidfile(pidfile.path()).unwrap();
+            let pid = std::fs::read_to_string(pidfile.path()).unwrap();
+            assert_eq!(pid.trim(), std::process::id().to_string());
+        }
+    }
+}
@@ -1,10 +1,13 @@
 pub mod r#async;
+use super::filter::Expr;
+use super::sort::SortSpec;
 use super::task;
-use super::task::{Step, Task};
+use super::task::{DateRange, Sprint, Step, Task};
 use chrono::prelude::*;
 use failure::Fail;
 use log::trace;
 use rusqlite::{params, Connection, Error};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 #[derive(Fail, Debug)]
@@ -13,6 +16,32 @@ pub enum DbError {
     DbFileNoParentDir,
     #[fail(display = "Failed to create the directory for the db file")]
     DbFileCreateParentDir,
+    #[fail(display = "Database error: {}", _0)]
+    Sqlite(#[cause] Error),
+    #[fail(
+        display = "Label {} conflicts with label {}: namespace {} is exclusive",
+        _0, _1, _2
+    )]
+    ExclusiveLabel(String, String, String),
+    #[fail(
+        display = "Namespace {} cannot be exclusive, these tasks have more than one label of it: {}",
+        _0, _1
+    )]
+    NamespaceConflict(String, String),
+    #[fail(display = "Step 0 is the implicit start step and it cannot be moved or replaced")]
+    StartStepReserved,
+    #[fail(display = "Sprint {} not found", _0)]
+    SprintNotFound(u32),
+    #[fail(display = "Sprint {} is already closed", _0)]
+    SprintClosed(u32),
+    #[fail(display = "Sprint {} cannot be the next sprint of itself", _0)]
+    SprintNextItself(u32),
+}
+
+impl From<Error> for DbError {
+    fn from(e: Error) -> Self {
+        DbError::Sqlite(e)
+    }
 }
 
 pub fn dbfile_default() -> PathBuf {
@@ -44,6 +73,12 @@ pub fn delete_tables(db: &Connection) -> Result<(), Error> {
     db.execute("DROP TABLE IF EXISTS status;", params![])?;
     db.execute("DROP TABLE IF EXISTS priority;", params![])?;
     db.execute("DROP TABLE IF EXISTS steps;", params![])?;
+    db.execute("DROP TABLE IF EXISTS label_namespace;", params![])?;
+    db.execute("DROP TABLE IF EXISTS todo_assignee;", params![])?;
+    db.execute("DROP TABLE IF EXISTS sprints;", params![])?;
+    db.execute("DROP TABLE IF EXISTS sprint_task;", params![])?;
+    db.execute("DROP TABLE IF EXISTS todo_due;", params![])?;
+    db.execute("DROP TABLE IF EXISTS todo_note;", params![])?;
     Ok(())
 }
 
@@ -111,6 +146,7 @@ pub fn init(filename: &Path, delete: bool) -> Result<(), Error> {
         todo_id INTEGER, steps_num INTEGER, descr varchar(1024), completion_date datetime );",
         params![],
     )?;
+    migrate(&c)?;
     let priority = vec!["urgent", "high", "normal", "low", "miserable"];
     for p in priority {
         match c.execute(
@@ -136,8 +172,68 @@ pub fn init(filename: &Path, delete: bool) -> Result<(), Error> {
     Ok(())
 }
 
+/// Create the tables added after the 0.3.1 release, so that an existing
+/// database gets the new features without being initialized again
+pub fn migrate(db: &Connection) -> Result<(), Error> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS label_namespace (
+        namespace varchar(32) PRIMARY KEY,
+        exclusive INTEGER );",
+        params![],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS todo_assignee (
+        todo_id INTEGER PRIMARY KEY,
+        assignee varchar(32) );",
+        params![],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS sprints (
+        id INTEGER PRIMARY KEY ASC,
+        name varchar(64),
+        start_date date,
+        end_date date,
+        capacity INTEGER,
+        closed_date datetime );",
+        params![],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS sprint_task (
+        sprint_id INTEGER,
+        todo_id INTEGER,
+        PRIMARY KEY (sprint_id,todo_id) );",
+        params![],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS todo_due (
+        todo_id INTEGER PRIMARY KEY,
+        due_date date );",
+        params![],
+    )?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS todo_note (
+        todo_id INTEGER PRIMARY KEY,
+        note text );",
+        params![],
+    )?;
+    Ok(())
+}
+
 pub fn get_db(filename: &Path) -> Result<Connection, Error> {
-    Connection::open(filename)
+    let c = Connection::open(filename)?;
+    migrate(&c)?;
+    Ok(c)
+}
+
+/// Cut a string to at most `max` bytes, on a char boundary
+fn truncate_chars(s: &mut String, max: usize) {
+    if s.len() > max {
+        let end = (0..=max)
+            .rev()
+            .find(|i| s.is_char_boundary(*i))
+            .unwrap_or(0);
+        s.truncate(end);
+    }
 }
 
 pub fn add_task(db: &Connection, descr: &str) -> Result<u32, Error> {
@@ -158,46 +254,153 @@ pub fn add_task(db: &Connection, descr: &str) -> Result<u32, Error> {
         VALUES (?1, ?2, ?3, ?4, 0);",
         params![&creation_date_str, &newdescr, &priority, &status],
     )?;
-    let new_id: u32 = db.query_row(
-        "SELECT id
-        FROM todos
-        WHERE creation_date = ?1;",
-        params![&creation_date_str],
-        |row| row.get(0),
-    )?;
-    Ok(new_id)
+    Ok(db.last_insert_rowid() as u32)
 }
 
-pub fn add_labels(db: &Connection, todo_id: u32, labels: &[String]) -> Result<(), Error> {
+/// Check that the new labels don't violate the exclusive namespace rules,
+/// considering the labels already attached to the task
+pub fn check_label_rules(
+    db: &Connection,
+    current: &[String],
+    labels: &[String],
+) -> Result<(), DbError> {
+    // the rules are read only if a label has a namespace
+    let mut exclusives = None;
+    let mut seen: Vec<&str> = current.iter().map(String::as_str).collect();
+    for l in labels {
+        let l = l.trim();
+        if let Some(ns) = task::label_namespace(l) {
+            if exclusives.is_none() {
+                exclusives = Some(get_exclusive_namespaces(db)?);
+            }
+            if exclusives.iter().flatten().any(|x| x == ns) {
+                if let Some(other) = seen
+                    .iter()
+                    .find(|x| **x != l && task::label_namespace(x) == Some(ns))
+                {
+                    return Err(DbError::ExclusiveLabel(
+                        l.to_string(),
+                        other.to_string(),
+                        ns.to_string(),
+                    ));
+                }
+            }
+        }
+        seen.push(l);
+    }
+    Ok(())
+}
+
+pub fn add_labels(db: &Connection, todo_id: u32, labels: &[String]) -> Result<(), DbError> {
+    let current = get_labels(db, todo_id)?;
+    check_label_rules(db, &current, labels)?;
     for l in labels {
         let mut ll = String::from(l.trim());
         ll.truncate(256);
-        match db.execute(
+        db.execute(
             "INSERT INTO todo_label (todo_id, label)
             VALUES (?1, ?2);",
             params![&todo_id, &ll],
-        ) {
-            Ok(_) => (),
-            Err(e) => return Err(e),
-        };
+        )?;
     }
     Ok(())
 }
 
-pub fn get_done_tasks(db: &Connection) -> Result<Vec<task::TaskDone>, Error> {
+/// The tasks with more than one label of the namespace
+pub fn get_namespace_conflicts(db: &Connection, namespace: &str) -> Result<Vec<u32>, Error> {
+    let mut stmt = db.prepare(
+        "SELECT todo_id, label
+        FROM todo_label;",
+    )?;
+    let query_iter = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let mut labels_per_task: BTreeMap<u32, usize> = BTreeMap::new();
+    for row in query_iter {
+        let (todo_id, label): (u32, String) = row?;
+        if task::label_namespace(&label) == Some(namespace) {
+            *labels_per_task.entry(todo_id).or_default() += 1;
+        }
+    }
+    Ok(labels_per_task
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .map(|(todo_id, _)| todo_id)
+        .collect())
+}
+
+/// Set the rule of a namespace
+/// A namespace cannot be made exclusive if a task has already more than one
+/// label of it
+pub fn set_namespace_exclusive(
+    db: &Connection,
+    namespace: &str,
+    exclusive: bool,
+) -> Result<(), DbError> {
+    if exclusive {
+        let conflicts = get_namespace_conflicts(db, namespace)?;
+        if !conflicts.is_empty() {
+            let ids: Vec<String> = conflicts.iter().map(u32::to_string).collect();
+            return Err(DbError::NamespaceConflict(
+                namespace.to_string(),
+                ids.join(", "),
+            ));
+        }
+    }
+    db.execute(
+        "INSERT OR REPLACE INTO label_namespace (namespace, exclusive)
+        VALUES (?1, ?2);",
+        params![&namespace, &exclusive],
+    )?;
+    Ok(())
+}
+
+pub fn get_exclusive_namespaces(db: &Connection) -> Result<Vec<String>, Error> {
     let mut stmt = db.prepare(
-        "SELECT t.id,t.descr,t.completion_date, t.story_points
+        "SELECT namespace
+        FROM label_namespace
+        WHERE exclusive = 1
+        ORDER BY namespace ASC;",
+    )?;
+    let query_iter = stmt.query_map(params![], |row| row.get(0))?;
+    let rc = query_iter.map(std::result::Result::unwrap).collect();
+    Ok(rc)
+}
+
+pub fn get_done_tasks(db: &Connection) -> Result<Vec<task::TaskDone>, Error> {
+    get_done_tasks_where(db, None, &DateRange::default(), &SortSpec::done_default())
+}
+
+/// Get the done tasks matching the filter and completed in the range, in the sort order
+pub fn get_done_tasks_where(
+    db: &Connection,
+    filter: Option<&Expr>,
+    range: &DateRange,
+    sort: &SortSpec,
+) -> Result<Vec<task::TaskDone>, Error> {
+    let mut values = Vec::new();
+    let condition = filter.map_or("1".to_string(), |f| f.to_sql(&mut values));
+    let range_condition = range.to_sql("t.completion_date", &mut values);
+    let mut stmt = db.prepare(&format!(
+        "SELECT t.id,t.descr,t.completion_date, t.story_points, a.assignee, p.descr, t.creation_date
         FROM todos t
+        LEFT JOIN priority p ON p.id = t.priority_id
         LEFT JOIN status s ON s.id = t.status_id
-        WHERE s.descr = \"done\"
-        ORDER BY completion_date ASC;",
-    )?;
-    let query_iter = stmt.query_map(params![], |row| {
+        LEFT JOIN todo_assignee a ON a.todo_id = t.id
+        LEFT JOIN todo_due d ON d.todo_id = t.id
+        WHERE s.descr = \"done\" AND {} AND {}
+        ORDER BY {};",
+        condition,
+        range_condition,
+        sort.to_sql()
+    ))?;
+    let query_iter = stmt.query_map(&values, |row| {
         Ok(task::TaskDone {
             id: row.get(0)?,
             descr: row.get(1)?,
             completion_date: row.get(2)?,
             storypoints: row.get(3).unwrap_or(0),
+            assignee: row.get(4)?,
+            priority: row.get(5)?,
+            creation_date: row.get(6)?,
         })
     })?;
     let rc = query_iter.map(std::result::Result::unwrap).collect();
@@ -205,27 +408,112 @@ pub fn get_done_tasks(db: &Connection) -> Result<Vec<task::TaskDone>, Error> {
 }
 
 pub fn get_open_tasks(db: &Connection) -> Result<Vec<Task>, Error> {
+    get_open_tasks_where(db, None, &SortSpec::open_default())
+}
+
+/// Get the open tasks matching the filter, in the sort order
+pub fn get_open_tasks_where(
+    db: &Connection,
+    filter: Option<&Expr>,
+    sort: &SortSpec,
+) -> Result<Vec<Task>, Error> {
+    let mut values = Vec::new();
+    let condition = filter.map_or("1".to_string(), |f| f.to_sql(&mut values));
+    let mut stmt = db.prepare(&format!(
+        "SELECT t.id,t.descr,p.descr,s.descr,t.story_points,a.assignee,d.due_date,t.creation_date
+        FROM todos t
+        LEFT JOIN priority p ON p.id = t.priority_id
+        LEFT JOIN status s ON s.id = t.status_id
+        LEFT JOIN todo_assignee a ON a.todo_id = t.id
+        LEFT JOIN todo_due d ON d.todo_id = t.id
+        WHERE completion_date IS NULL AND {}
+        ORDER BY {};",
+        condition,
+        sort.to_sql()
+    ))?;
+    let query_iter = stmt.query_map(&values, task_from_row)?;
+    let rc = query_iter.map(std::result::Result::unwrap).collect();
+    Ok(rc)
+}
+
+/// Get all the tasks, open and done, by id
+pub fn get_all_tasks(db: &Connection) -> Result<Vec<Task>, Error> {
     let mut stmt = db.prepare(
-        "SELECT t.id,t.descr,p.descr,s.descr,t.story_points
+        "SELECT t.id,t.descr,p.descr,s.descr,t.story_points,a.assignee,d.due_date,t.creation_date
         FROM todos t
         LEFT JOIN priority p ON p.id = t.priority_id
         LEFT JOIN status s ON s.id = t.status_id
-        WHERE completion_date IS NULL
-        ORDER BY t.priority_id ASC;",
+        LEFT JOIN todo_assignee a ON a.todo_id = t.id
+        LEFT JOIN todo_due d ON d.todo_id = t.id
+        ORDER BY t.id ASC;",
     )?;
-    let query_iter = stmt.query_map(params![], |row| {
-        Ok(task::Task {
-            id: row.get(0)?,
-            descr: row.get(1)?,
-            priority: row.get(2)?,
-            status: row.get(3)?,
-            storypoints: row.get(4).unwrap_or(0),
-        })
-    })?;
+    let query_iter = stmt.query_map(params![], task_from_row)?;
     let rc = query_iter.map(std::result::Result::unwrap).collect();
     Ok(rc)
 }
 
+/// Get a task, open or done, by id
+pub fn get_task(db: &Connection, todo_id: u32) -> Result<Task, Error> {
+    db.query_row(
+        "SELECT t.id,t.descr,p.descr,s.descr,t.story_points,a.assignee,d.due_date,t.creation_date
+        FROM todos t
+        LEFT JOIN priority p ON p.id = t.priority_id
+        LEFT JOIN status s ON s.id = t.status_id
+        LEFT JOIN todo_assignee a ON a.todo_id = t.id
+        LEFT JOIN todo_due d ON d.todo_id = t.id
+        WHERE t.id = ?1;",
+        params![&todo_id],
+        task_from_row,
+    )
+}
+
+/// Change the id of a task, before anything else refers to it
+pub fn set_task_id(db: &Connection, todo_id: u32, new_id: u32) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE todos
+        SET id = ?1
+        WHERE id = ?2;",
+        params![&new_id, &todo_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+/// Delete all the tasks, with their steps, labels and sprints
+pub fn clear_tasks(db: &Connection) -> Result<(), Error> {
+    for table in &[
+        "todos",
+        "todo_label",
+        "refs",
+        "steps",
+        "label_namespace",
+        "todo_assignee",
+        "sprints",
+        "sprint_task",
+        "todo_due",
+        "todo_note",
+    ] {
+        db.execute(&format!("DELETE FROM {};", table), params![])?;
+    }
+    Ok(())
+}
+
+fn task_from_row(row: &rusqlite::Row) -> Result<Task, Error> {
+    Ok(task::Task {
+        id: row.get(0)?,
+        descr: row.get(1)?,
+        priority: row.get(2)?,
+        status: row.get(3)?,
+        storypoints: row.get(4).unwrap_or(0),
+        assignee: row.get(5)?,
+        due_date: row.get(6)?,
+        creation_date: row.get(7)?,
+    })
+}
+
 pub fn get_labels(db: &Connection, todo_id: u32) -> Result<Vec<String>, Error> {
     let mut stmt = db.prepare(
         "SELECT label
@@ -276,6 +564,42 @@ pub fn complete_task(db: &Connection, todo_id: u32) -> Result<(), Error> {
     }
 }
 
+/// Reopen a completed task, setting it back in progress
+pub fn reopen_task(db: &Connection, todo_id: u32) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE todos
+        SET completion_date = NULL
+        WHERE id = ?1;",
+        params![&todo_id],
+    )?;
+    if rc != 1 {
+        return Err(Error::QueryReturnedNoRows);
+    }
+    set_status(db, todo_id, "in_progress")
+}
+
+pub fn get_task_status(db: &Connection, todo_id: u32) -> Result<String, Error> {
+    db.query_row(
+        "SELECT s.descr
+        FROM todos t
+        LEFT JOIN status s ON s.id = t.status_id
+        WHERE t.id = ?1;",
+        params![&todo_id],
+        |row| row.get(0),
+    )
+}
+
+/// Get the completion date of a task, None if the task is open
+pub fn get_task_completion_date(db: &Connection, todo_id: u32) -> Result<Option<String>, Error> {
+    db.query_row(
+        "SELECT completion_date
+        FROM todos
+        WHERE id = ?1;",
+        params![&todo_id],
+        |row| row.get(0),
+    )
+}
+
 pub fn delete_task(db: &Connection, todo_id: u32) -> Result<(), Error> {
     let rc = db.execute(
         "DELETE FROM todos
@@ -287,6 +611,26 @@ pub fn delete_task(db: &Connection, todo_id: u32) -> Result<(), Error> {
         WHERE todo_id = ?1;",
         &[&todo_id],
     )?;
+    db.execute(
+        "DELETE FROM todo_assignee
+        WHERE todo_id = ?1;",
+        &[&todo_id],
+    )?;
+    db.execute(
+        "DELETE FROM sprint_task
+        WHERE todo_id = ?1;",
+        &[&todo_id],
+    )?;
+    db.execute(
+        "DELETE FROM todo_due
+        WHERE todo_id = ?1;",
+        &[&todo_id],
+    )?;
+    db.execute(
+        "DELETE FROM todo_note
+        WHERE todo_id = ?1;",
+        &[&todo_id],
+    )?;
     if rc != 1 {
         Err(Error::StatementChangedRows(rc))
     } else {
@@ -294,6 +638,122 @@ pub fn delete_task(db: &Connection, todo_id: u32) -> Result<(), Error> {
     }
 }
 
+/// Set the assignee of a task, None to unassign it
+pub fn set_assignee(db: &Connection, todo_id: u32, assignee: Option<&str>) -> Result<(), Error> {
+    get_task_status(db, todo_id)?;
+    match assignee {
+        Some(assignee) => {
+            let mut newassignee = String::from(assignee.trim());
+            truncate_chars(&mut newassignee, 32);
+            db.execute(
+                "INSERT OR REPLACE INTO todo_assignee (todo_id, assignee)
+                VALUES (?1, ?2);",
+                params![&todo_id, &newassignee],
+            )?;
+        }
+        None => {
+            db.execute(
+                "DELETE FROM todo_assignee
+                WHERE todo_id = ?1;",
+                params![&todo_id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Set the due date of a task, None to remove it
+pub fn set_due_date(
+    db: &Connection,
+    todo_id: u32,
+    due_date: Option<&NaiveDate>,
+) -> Result<(), Error> {
+    get_task_status(db, todo_id)?;
+    match due_date {
+        Some(due_date) => {
+            db.execute(
+                "INSERT OR REPLACE INTO todo_due (todo_id, due_date)
+                VALUES (?1, ?2);",
+                params![&todo_id, &due_date.format("%Y-%m-%d").to_string()],
+            )?;
+        }
+        None => {
+            db.execute(
+                "DELETE FROM todo_due
+                WHERE todo_id = ?1;",
+                params![&todo_id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Set the note of a task, a longer free text; None to remove it
+pub fn set_note(db: &Connection, todo_id: u32, note: Option<&str>) -> Result<(), Error> {
+    get_task_status(db, todo_id)?;
+    match note {
+        Some(note) => {
+            db.execute(
+                "INSERT OR REPLACE INTO todo_note (todo_id, note)
+                VALUES (?1, ?2);",
+                params![&todo_id, &note.trim_end()],
+            )?;
+        }
+        None => {
+            db.execute(
+                "DELETE FROM todo_note
+                WHERE todo_id = ?1;",
+                params![&todo_id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn get_note(db: &Connection, todo_id: u32) -> Result<Option<String>, Error> {
+    let rc = db.query_row(
+        "SELECT note
+        FROM todo_note
+        WHERE todo_id = ?1;",
+        params![&todo_id],
+        |row| row.get(0),
+    );
+    match rc {
+        Err(Error::QueryReturnedNoRows) => Ok(None),
+        rc => rc,
+    }
+}
+
+/// Overwrite the creation date of a task, used when tasks are imported
+pub fn set_creation_date(db: &Connection, todo_id: u32, date: &str) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE todos
+        SET creation_date = ?1
+        WHERE id = ?2;",
+        params![&date, &todo_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+/// Overwrite the completion date of a task, used when tasks are imported
+pub fn set_completion_date(db: &Connection, todo_id: u32, date: &str) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE todos
+        SET completion_date = ?1
+        WHERE id = ?2;",
+        params![&date, &todo_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
 pub fn get_priority_id(db: &Connection, priority: &str) -> Result<u32, Error> {
     trace!("get priority id ({})", priority);
     let priority_id: u32 = db.query_row(
@@ -404,6 +864,13 @@ pub fn increase_priority(db: &Connection, todo_id: u32) -> Result<(), Error> {
     }
 }
 
+/// The description of a step as it is stored, trimmed and cut to the column size
+fn step_descr(step_description: &str) -> String {
+    let mut descr = String::from(step_description.trim_end());
+    truncate_chars(&mut descr, 1024);
+    descr
+}
+
 pub fn add_step(db: &Connection, todo_id: u32, step_description: &str) -> Result<u32, Error> {
     let new_step: u32 = match db.query_row(
         "SELECT MAX(steps_num)
@@ -418,11 +885,26 @@ pub fn add_step(db: &Connection, todo_id: u32, step_description: &str) -> Result
     db.execute(
         "INSERT INTO steps (todo_id, steps_num, descr)
         VALUES (?1, ?2, ?3);",
-        params![&todo_id, &new_step, &step_description],
+        params![&todo_id, &new_step, &step_descr(step_description)],
     )?;
     Ok(new_step)
 }
 
+/// Get the date a task was started, the completion date of the start step (step 0)
+pub fn get_start_date(db: &Connection, todo_id: u32) -> Result<Option<String>, Error> {
+    let rc = db.query_row(
+        "SELECT completion_date
+        FROM steps
+        WHERE todo_id = ?1 AND steps_num = 0;",
+        params![&todo_id],
+        |row| row.get(0),
+    );
+    match rc {
+        Err(Error::QueryReturnedNoRows) => Ok(None),
+        rc => rc,
+    }
+}
+
 pub fn get_step(db: &Connection, todo_id: u32, step_id: u32) -> Result<Step, Error> {
     let mut stmt = db.prepare(
         "SELECT todo_id,steps_num,descr
@@ -464,6 +946,46 @@ pub fn get_steps(db: &Connection, todo_id: u32) -> Result<Vec<Step>, Error> {
     Ok(result)
 }
 
+/// Get all the steps of a task, the completed ones with their completion date
+pub fn get_all_steps(db: &Connection, todo_id: u32) -> Result<Vec<Step>, Error> {
+    let mut stmt = db.prepare(
+        "SELECT todo_id,steps_num,descr,completion_date
+        FROM steps
+        WHERE todo_id = ?1
+        ORDER BY steps_num ASC;",
+    )?;
+    let query_iter = stmt.query_map(params![&todo_id], |row| {
+        Ok(Step {
+            todo_id: row.get(0)?,
+            step_id: row.get(1)?,
+            descr: row.get(2)?,
+            completion_date: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+        })
+    })?;
+    let result = query_iter.map(std::result::Result::unwrap).collect();
+    Ok(result)
+}
+
+/// Overwrite the completion date of a step, used when tasks are imported
+pub fn set_step_completion_date(
+    db: &Connection,
+    todo_id: u32,
+    step_id: u32,
+    date: &str,
+) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE steps
+        SET completion_date = ?1
+        WHERE todo_id = ?2 AND steps_num = ?3;",
+        params![&date, &todo_id, &step_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
 pub fn complete_step(db: &Connection, todo_id: u32, step_id: u32) -> Result<(), Error> {
     let completion_date: DateTime<Utc> = Utc::now();
     let completion_date_str = completion_date.format("%Y-%m-%d %H:%M:%S").to_string();
@@ -480,14 +1002,23 @@ pub fn complete_step(db: &Connection, todo_id: u32, step_id: u32) -> Result<(),
     }
 }
 
+/// Complete the open steps of a completed task
+/// The start step (step 0) is left open: it is completed only when the task is
+/// started, so it records whether the task was started at all
 pub fn complete_steps(db: &Connection, todo_id: u32) -> Result<(), Error> {
     let completion_date: DateTime<Utc> = Utc::now();
     let completion_date_str = completion_date.format("%Y-%m-%d %H:%M:%S").to_string();
+    complete_steps_at(db, todo_id, &completion_date_str)
+}
+
+/// Complete the open steps but the start step at the given date, used when
+/// tasks are imported
+pub fn complete_steps_at(db: &Connection, todo_id: u32, date: &str) -> Result<(), Error> {
     db.execute(
         "UPDATE steps
         SET completion_date = ?1
-        WHERE completion_date IS NULL AND todo_id = ?2 ;",
-        params![&completion_date_str, &todo_id],
+        WHERE completion_date IS NULL AND todo_id = ?2 AND steps_num > 0;",
+        params![&date, &todo_id],
     )?;
     Ok(())
 }
@@ -501,6 +1032,155 @@ pub fn delete_step(db: &Connection, todo_id: u32, step_id: u32) -> Result<(), Er
     Ok(())
 }
 
+pub fn reopen_step(db: &Connection, todo_id: u32, step_id: u32) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE steps
+        SET completion_date = NULL
+        WHERE todo_id = ?1 AND steps_num = ?2;",
+        params![&todo_id, &step_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+/// Check if all the steps of a task are completed
+/// A task with only the start step (step 0) has no steps to complete
+pub fn all_steps_done(db: &Connection, todo_id: u32) -> Result<bool, Error> {
+    let (done, steps) = get_steps_progress(db, todo_id)?;
+    Ok(steps > 0 && done == steps)
+}
+
+/// Get the number of completed steps and the number of steps of a task
+/// The start step (step 0) is not counted
+pub fn get_steps_progress(db: &Connection, todo_id: u32) -> Result<(u32, u32), Error> {
+    db.query_row(
+        "SELECT COUNT(completion_date), COUNT(*)
+        FROM steps
+        WHERE todo_id = ?1 AND steps_num > 0;",
+        params![&todo_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+pub fn set_step_descr(
+    db: &Connection,
+    todo_id: u32,
+    step_id: u32,
+    step_description: &str,
+) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE steps
+        SET descr = ?1
+        WHERE todo_id = ?2 AND steps_num = ?3;",
+        params![&step_descr(step_description), &todo_id, &step_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+/// Insert a new step before an existing one, the following steps are shifted
+pub fn insert_step(
+    db: &Connection,
+    todo_id: u32,
+    before: u32,
+    step_description: &str,
+) -> Result<u32, DbError> {
+    if before == 0 {
+        return Err(DbError::StartStepReserved);
+    }
+    get_step(db, todo_id, before)?;
+    db.execute(
+        "UPDATE steps
+        SET steps_num = steps_num + 1
+        WHERE todo_id = ?1 AND steps_num >= ?2;",
+        params![&todo_id, &before],
+    )?;
+    db.execute(
+        "INSERT INTO steps (todo_id, steps_num, descr)
+        VALUES (?1, ?2, ?3);",
+        params![&todo_id, &before, &step_descr(step_description)],
+    )?;
+    Ok(before)
+}
+
+/// Move a step to another position, the steps in between are shifted
+/// Returns the new position of the step
+pub fn move_step(db: &Connection, todo_id: u32, step_id: u32, to: u32) -> Result<u32, DbError> {
+    if step_id == 0 || to == 0 {
+        return Err(DbError::StartStepReserved);
+    }
+    get_step(db, todo_id, step_id)?;
+    let max_step: u32 = db.query_row(
+        "SELECT MAX(steps_num)
+        FROM steps
+        WHERE todo_id = ?1;",
+        params![&todo_id],
+        |row| row.get(0),
+    )?;
+    let to = to.min(max_step);
+    if to == step_id {
+        return Ok(to);
+    }
+    db.execute(
+        "UPDATE steps
+        SET steps_num = -1
+        WHERE todo_id = ?1 AND steps_num = ?2;",
+        params![&todo_id, &step_id],
+    )?;
+    if to > step_id {
+        db.execute(
+            "UPDATE steps
+            SET steps_num = steps_num - 1
+            WHERE todo_id = ?1 AND steps_num > ?2 AND steps_num <= ?3;",
+            params![&todo_id, &step_id, &to],
+        )?;
+    } else {
+        db.execute(
+            "UPDATE steps
+            SET steps_num = steps_num + 1
+            WHERE todo_id = ?1 AND steps_num >= ?2 AND steps_num < ?3;",
+            params![&todo_id, &to, &step_id],
+        )?;
+    }
+    db.execute(
+        "UPDATE steps
+        SET steps_num = ?2
+        WHERE todo_id = ?1 AND steps_num = -1;",
+        params![&todo_id, &to],
+    )?;
+    Ok(to)
+}
+
+/// Renumber the steps of a task, removing the gaps
+/// The start step (step 0) is not renumbered
+pub fn renumber_steps(db: &Connection, todo_id: u32) -> Result<u32, Error> {
+    let mut stmt = db.prepare(
+        "SELECT rowid
+        FROM steps
+        WHERE todo_id = ?1 AND steps_num > 0
+        ORDER BY steps_num ASC, rowid ASC;",
+    )?;
+    let query_iter = stmt.query_map(params![&todo_id], |row| row.get(0))?;
+    let rowids: Vec<i64> = query_iter.map(std::result::Result::unwrap).collect();
+    let mut step_num = 0;
+    for rowid in rowids {
+        step_num += 1;
+        db.execute(
+            "UPDATE steps
+            SET steps_num = ?1
+            WHERE rowid = ?2;",
+            params![&step_num, &rowid],
+        )?;
+    }
+    Ok(step_num)
+}
+
 pub fn delete_steps(db: &Connection, todo_id: u32) -> Result<(), Error> {
     db.execute(
         "DELETE FROM steps
@@ -541,6 +1221,216 @@ pub fn set_reference(db: &Connection, todo_id: u32, reference: &str) -> Result<(
     }
 }
 
+pub fn add_sprint(
+    db: &Connection,
+    name: &str,
+    start_date: &NaiveDate,
+    end_date: &NaiveDate,
+    capacity: u32,
+) -> Result<u32, Error> {
+    let mut newname = String::from(name.trim_end());
+    truncate_chars(&mut newname, 64);
+    db.execute(
+        "INSERT INTO sprints (name, start_date, end_date, capacity)
+        VALUES (?1, ?2, ?3, ?4);",
+        params![
+            &newname,
+            &start_date.format("%Y-%m-%d").to_string(),
+            &end_date.format("%Y-%m-%d").to_string(),
+            &capacity
+        ],
+    )?;
+    Ok(db.last_insert_rowid() as u32)
+}
+
+/// Change the id of a sprint, before any task is added to it
+pub fn set_sprint_id(db: &Connection, sprint_id: u32, new_id: u32) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE sprints
+        SET id = ?1
+        WHERE id = ?2;",
+        params![&new_id, &sprint_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+/// Overwrite the closing date of a sprint, used when sprints are imported
+pub fn set_sprint_closed_date(db: &Connection, sprint_id: u32, date: &str) -> Result<(), Error> {
+    let rc = db.execute(
+        "UPDATE sprints
+        SET closed_date = ?1
+        WHERE id = ?2;",
+        params![&date, &sprint_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+fn sprint_from_row(row: &rusqlite::Row) -> Result<Sprint, Error> {
+    Ok(Sprint {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        start_date: row.get(2)?,
+        end_date: row.get(3)?,
+        capacity: row.get(4).unwrap_or(0),
+        closed_date: row.get(5)?,
+    })
+}
+
+pub fn get_sprint(db: &Connection, sprint_id: u32) -> Result<Sprint, Error> {
+    db.query_row(
+        "SELECT id,name,start_date,end_date,capacity,closed_date
+        FROM sprints
+        WHERE id = ?1;",
+        params![&sprint_id],
+        sprint_from_row,
+    )
+}
+
+pub fn get_sprints(db: &Connection) -> Result<Vec<Sprint>, Error> {
+    let mut stmt = db.prepare(
+        "SELECT id,name,start_date,end_date,capacity,closed_date
+        FROM sprints
+        ORDER BY start_date ASC, id ASC;",
+    )?;
+    let query_iter = stmt.query_map(params![], sprint_from_row)?;
+    let rc = query_iter.map(std::result::Result::unwrap).collect();
+    Ok(rc)
+}
+
+/// The active sprint is the first sprint, by start date, not yet closed
+pub fn get_active_sprint(db: &Connection) -> Result<Sprint, Error> {
+    db.query_row(
+        "SELECT id,name,start_date,end_date,capacity,closed_date
+        FROM sprints
+        WHERE closed_date IS NULL
+        ORDER BY start_date ASC, id ASC
+        LIMIT 1;",
+        params![],
+        sprint_from_row,
+    )
+}
+
+pub fn add_sprint_task(db: &Connection, sprint_id: u32, todo_id: u32) -> Result<(), Error> {
+    get_sprint(db, sprint_id)?;
+    get_task_status(db, todo_id)?;
+    db.execute(
+        "INSERT OR IGNORE INTO sprint_task (sprint_id, todo_id)
+        VALUES (?1, ?2);",
+        params![&sprint_id, &todo_id],
+    )?;
+    Ok(())
+}
+
+pub fn remove_sprint_task(db: &Connection, sprint_id: u32, todo_id: u32) -> Result<(), Error> {
+    let rc = db.execute(
+        "DELETE FROM sprint_task
+        WHERE sprint_id = ?1 AND todo_id = ?2;",
+        params![&sprint_id, &todo_id],
+    )?;
+    if rc != 1 {
+        Err(Error::QueryReturnedNoRows)
+    } else {
+        Ok(())
+    }
+}
+
+/// Get the tasks of a sprint, with a flag telling if the task was completed
+/// during the sprint (before the sprint was closed)
+pub fn get_sprint_tasks(
+    db: &Connection,
+    sprint_id: u32,
+    sort: &SortSpec,
+) -> Result<Vec<(Task, bool)>, Error> {
+    let mut stmt = db.prepare(&format!(
+        "SELECT t.id,t.descr,p.descr,s.descr,t.story_points,a.assignee,d.due_date,t.creation_date,
+        t.completion_date IS NOT NULL
+            AND (sp.closed_date IS NULL OR t.completion_date <= sp.closed_date)
+        FROM sprint_task st
+        JOIN sprints sp ON sp.id = st.sprint_id
+        JOIN todos t ON t.id = st.todo_id
+        LEFT JOIN priority p ON p.id = t.priority_id
+        LEFT JOIN status s ON s.id = t.status_id
+        LEFT JOIN todo_assignee a ON a.todo_id = t.id
+        LEFT JOIN todo_due d ON d.todo_id = t.id
+        WHERE st.sprint_id = ?1
+        ORDER BY {};",
+        sort.to_sql()
+    ))?;
+    let query_iter = stmt.query_map(params![&sprint_id], |row| {
+        Ok((task_from_row(row)?, row.get(8)?))
+    })?;
+    let rc = query_iter.map(std::result::Result::unwrap).collect();
+    Ok(rc)
+}
+
+/// Close a sprint, rolling the unfinished tasks over to the next sprint
+/// If `next` is not specified, the next sprint is the first open one after it
+/// The next sprint is checked before closing, the caller runs both in a transaction
+/// Returns the next sprint id, if any
+pub fn complete_sprint(
+    db: &Connection,
+    sprint_id: u32,
+    next: Option<u32>,
+) -> Result<Option<u32>, DbError> {
+    let is_closed = |id: u32| match get_sprint(db, id) {
+        Ok(sprint) => Ok(sprint.closed_date.is_some()),
+        Err(Error::QueryReturnedNoRows) => Err(DbError::SprintNotFound(id)),
+        Err(e) => Err(e.into()),
+    };
+    if is_closed(sprint_id)? {
+        return Err(DbError::SprintClosed(sprint_id));
+    }
+    let next = match next {
+        Some(next) if next == sprint_id => return Err(DbError::SprintNextItself(sprint_id)),
+        Some(next) => {
+            if is_closed(next)? {
+                return Err(DbError::SprintClosed(next));
+            }
+            Some(next)
+        }
+        None => match db.query_row(
+            "SELECT id
+            FROM sprints
+            WHERE closed_date IS NULL AND id != ?1
+            ORDER BY start_date ASC, id ASC
+            LIMIT 1;",
+            params![&sprint_id],
+            |row| row.get(0),
+        ) {
+            Ok(next) => Some(next),
+            Err(Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        },
+    };
+    let completion_date: DateTime<Utc> = Utc::now();
+    let completion_date_str = completion_date.format("%Y-%m-%d %H:%M:%S").to_string();
+    db.execute(
+        "UPDATE sprints
+        SET closed_date = ?1
+        WHERE id = ?2;",
+        params![&completion_date_str, &sprint_id],
+    )?;
+    if let Some(next) = next {
+        db.execute(
+            "INSERT OR IGNORE INTO sprint_task (sprint_id, todo_id)
+            SELECT ?1, st.todo_id
+            FROM sprint_task st
+            JOIN todos t ON t.id = st.todo_id
+            WHERE st.sprint_id = ?2 AND t.completion_date IS NULL;",
+            params![&next, &sprint_id],
+        )?;
+    }
+    Ok(next)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -626,6 +1516,53 @@ mod test {
         get_db(dbfile.path()).unwrap();
     }
 
+    const TABLES_AFTER_0_3_1: [&str; 6] = [
+        "label_namespace",
+        "todo_assignee",
+        "sprints",
+        "sprint_task",
+        "todo_due",
+        "todo_note",
+    ];
+
+    #[test]
+    fn test_migrate() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        // a database created by 0.3.1
+        {
+            let db = Connection::open(dbfile.path()).unwrap();
+            for table in TABLES_AFTER_0_3_1.iter() {
+                db.execute(&format!("DROP TABLE {};", table), params![])
+                    .unwrap();
+            }
+        }
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        add_labels(&db, id, &["size:S".to_string()]).unwrap();
+        set_namespace_exclusive(&db, "size", true).unwrap();
+        set_assignee(&db, id, Some("alice")).unwrap();
+        assert_eq!(
+            get_open_tasks(&db).unwrap()[0].assignee,
+            Some("alice".to_string())
+        );
+        let today = Local::today().naive_local();
+        let sprint = add_sprint(&db, "s1", &today, &today, 10).unwrap();
+        add_sprint_task(&db, sprint, id).unwrap();
+        assert_eq!(get_active_sprint(&db).unwrap().id, sprint);
+        set_due_date(&db, id, Some(&today)).unwrap();
+        assert_eq!(
+            get_open_tasks_where(&db, None, &SortSpec::open_default()).unwrap()[0].due_date,
+            Some(today.format("%Y-%m-%d").to_string())
+        );
+        set_note(&db, id, Some("a note")).unwrap();
+        assert_eq!(get_note(&db, id).unwrap(), Some("a note".to_string()));
+        // the tables are created only once
+        migrate(&db).unwrap();
+        assert_eq!(get_exclusive_namespaces(&db).unwrap(), vec!["size"]);
+    }
+
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(5))]
         #[test]
@@ -671,4 +1608,426 @@ mod test {
             assert_eq!( get_status_id(&db, s).is_err(), true);
         }
     }
+
+    #[test]
+    fn test_exclusive_namespace_1() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        add_labels(&db, id, &["size:S".to_string(), "size:L".to_string()]).unwrap();
+        let id2 = add_task(&db, "test2").unwrap();
+        add_labels(&db, id2, &["size:M".to_string(), "area:web".to_string()]).unwrap();
+        assert_eq!(get_namespace_conflicts(&db, "size").unwrap(), vec![id]);
+        match set_namespace_exclusive(&db, "size", true) {
+            Err(DbError::NamespaceConflict(ns, ids)) => {
+                assert_eq!(ns, "size");
+                assert_eq!(ids, id.to_string());
+            }
+            _ => panic!("conflicting labels not detected"),
+        }
+        assert!(get_exclusive_namespaces(&db).unwrap().is_empty());
+        set_namespace_exclusive(&db, "area", true).unwrap();
+        assert_eq!(get_exclusive_namespaces(&db).unwrap(), vec!["area"]);
+        set_namespace_exclusive(&db, "area", false).unwrap();
+        assert!(get_exclusive_namespaces(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_exclusive_namespace_2() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        set_namespace_exclusive(&db, "size", true).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        add_labels(&db, id, &["size:S".to_string(), "area:backend".to_string()]).unwrap();
+        add_labels(&db, id, &["area:infra".to_string()]).unwrap();
+        match add_labels(&db, id, &["size:L".to_string()]) {
+            Err(DbError::ExclusiveLabel(_, _, ns)) => assert_eq!(ns, "size"),
+            _ => panic!("exclusive namespace not enforced"),
+        }
+        assert!(
+            check_label_rules(&db, &[], &["size:S".to_string(), "size:L".to_string()]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_add_task_same_second() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        // created in the same second, the creation date does not identify them
+        let ids: Vec<u32> = (0..3)
+            .map(|i| add_task(&db, &format!("task {}", i)).unwrap())
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    fn get_step_descrs(db: &Connection, todo_id: u32) -> Vec<(u32, String)> {
+        get_steps(db, todo_id)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.step_id, s.descr))
+            .collect()
+    }
+
+    #[test]
+    fn test_steps_edit() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        assert_eq!(add_step(&db, id, "start").unwrap(), 0);
+        assert_eq!(add_step(&db, id, "a").unwrap(), 1);
+        assert_eq!(add_step(&db, id, "b").unwrap(), 2);
+        assert_eq!(add_step(&db, id, "c").unwrap(), 3);
+        set_step_descr(&db, id, 2, "B").unwrap();
+        assert!(set_step_descr(&db, id, 7, "X").is_err());
+        assert_eq!(insert_step(&db, id, 2, "a2").unwrap(), 2);
+        assert!(insert_step(&db, id, 0, "x").is_err());
+        assert_eq!(
+            get_step_descrs(&db, id),
+            vec![
+                (0, "start".to_string()),
+                (1, "a".to_string()),
+                (2, "a2".to_string()),
+                (3, "B".to_string()),
+                (4, "c".to_string())
+            ]
+        );
+        // the descriptions are stored the same way by add, insert and edit
+        let long = "日".repeat(342);
+        assert_eq!(add_step(&db, id, "d  ").unwrap(), 5);
+        assert_eq!(insert_step(&db, id, 5, &long).unwrap(), 5);
+        set_step_descr(&db, id, 4, "C  ").unwrap();
+        let steps = get_step_descrs(&db, id);
+        assert_eq!(steps[4], (4, "C".to_string()));
+        assert_eq!(steps[5], (5, "日".repeat(341)));
+        assert_eq!(steps[6], (6, "d".to_string()));
+    }
+
+    #[test]
+    fn test_steps_move_renumber() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        for d in &["start", "a", "b", "c", "d"] {
+            add_step(&db, id, d).unwrap();
+        }
+        assert_eq!(move_step(&db, id, 1, 3).unwrap(), 3);
+        assert_eq!(move_step(&db, id, 4, 1).unwrap(), 1);
+        assert_eq!(move_step(&db, id, 2, 99).unwrap(), 4);
+        assert!(move_step(&db, id, 0, 2).is_err());
+        assert!(move_step(&db, id, 2, 0).is_err());
+        let descrs: Vec<String> = get_step_descrs(&db, id).into_iter().map(|x| x.1).collect();
+        assert_eq!(descrs, vec!["start", "d", "c", "a", "b"]);
+        delete_step(&db, id, 2).unwrap();
+        assert_eq!(renumber_steps(&db, id).unwrap(), 3);
+        assert_eq!(
+            get_step_descrs(&db, id),
+            vec![
+                (0, "start".to_string()),
+                (1, "d".to_string()),
+                (2, "a".to_string()),
+                (3, "b".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_steps_done() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        add_step(&db, id, "start").unwrap();
+        complete_step(&db, id, 0).unwrap();
+        assert!(!all_steps_done(&db, id).unwrap());
+        add_step(&db, id, "a").unwrap();
+        add_step(&db, id, "b").unwrap();
+        complete_step(&db, id, 1).unwrap();
+        assert!(!all_steps_done(&db, id).unwrap());
+        complete_step(&db, id, 2).unwrap();
+        assert!(all_steps_done(&db, id).unwrap());
+        reopen_step(&db, id, 2).unwrap();
+        assert!(!all_steps_done(&db, id).unwrap());
+    }
+
+    #[test]
+    fn test_reopen_task() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        complete_task(&db, id).unwrap();
+        set_status(&db, id, "done").unwrap();
+        assert_eq!(get_task_status(&db, id).unwrap(), "done");
+        assert!(get_open_tasks(&db).unwrap().is_empty());
+        reopen_task(&db, id).unwrap();
+        assert_eq!(get_task_status(&db, id).unwrap(), "in_progress");
+        assert_eq!(get_open_tasks(&db).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_assignee() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        assert_eq!(get_open_tasks(&db).unwrap()[0].assignee, None);
+        set_assignee(&db, id, Some("alice")).unwrap();
+        assert_eq!(
+            get_open_tasks(&db).unwrap()[0].assignee,
+            Some("alice".to_string())
+        );
+        set_assignee(&db, id, Some("bob")).unwrap();
+        assert_eq!(
+            get_open_tasks(&db).unwrap()[0].assignee,
+            Some("bob".to_string())
+        );
+        set_assignee(&db, id, None).unwrap();
+        assert_eq!(get_open_tasks(&db).unwrap()[0].assignee, None);
+        assert!(set_assignee(&db, id + 1, Some("alice")).is_err());
+        // 36 bytes, the 32nd is inside a character
+        set_assignee(&db, id, Some("日本語日本語日本語日本語")).unwrap();
+        assert_eq!(
+            get_open_tasks(&db).unwrap()[0].assignee,
+            Some("日本語日本語日本語日".to_string())
+        );
+    }
+
+    #[test]
+    fn test_note() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let id = add_task(&db, "test").unwrap();
+        assert_eq!(get_note(&db, id).unwrap(), None);
+        set_note(&db, id, Some("first line\nsecond line\n")).unwrap();
+        assert_eq!(
+            get_note(&db, id).unwrap(),
+            Some("first line\nsecond line".to_string())
+        );
+        set_note(&db, id, None).unwrap();
+        assert_eq!(get_note(&db, id).unwrap(), None);
+        assert!(set_note(&db, id + 1, Some("note")).is_err());
+    }
+
+    #[test]
+    fn test_sprint() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let s1 = add_sprint(
+            &db,
+            "first",
+            &NaiveDate::from_ymd(2019, 5, 6),
+            &NaiveDate::from_ymd(2019, 5, 17),
+            10,
+        )
+        .unwrap();
+        let s2 = add_sprint(
+            &db,
+            "second",
+            &NaiveDate::from_ymd(2019, 5, 20),
+            &NaiveDate::from_ymd(2019, 5, 31),
+            10,
+        )
+        .unwrap();
+        assert_eq!(get_active_sprint(&db).unwrap().id, s1);
+        let t1 = add_task(&db, "one").unwrap();
+        set_storypoint(&db, t1, 3).unwrap();
+        let t2 = add_task(&db, "two").unwrap();
+        set_storypoint(&db, t2, 5).unwrap();
+        add_sprint_task(&db, s1, t1).unwrap();
+        add_sprint_task(&db, s1, t2).unwrap();
+        assert!(add_sprint_task(&db, s1, t2 + 1).is_err());
+        complete_task(&db, t1).unwrap();
+        let tasks = get_sprint_tasks(&db, s1, &SortSpec::open_default()).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().any(|(t, done)| t.id == t1 && *done));
+        assert!(tasks.iter().any(|(t, done)| t.id == t2 && !*done));
+        // a wrong next sprint leaves the sprint open
+        match complete_sprint(&db, s1, Some(s1)) {
+            Err(DbError::SprintNextItself(id)) => assert_eq!(id, s1),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match complete_sprint(&db, s1, Some(s2 + 1)) {
+            Err(DbError::SprintNotFound(id)) => assert_eq!(id, s2 + 1),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(get_sprint(&db, s1).unwrap().closed_date.is_none());
+        assert_eq!(complete_sprint(&db, s1, None).unwrap(), Some(s2));
+        match complete_sprint(&db, s1, None) {
+            Err(DbError::SprintClosed(id)) => assert_eq!(id, s1),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match complete_sprint(&db, s2, Some(s1)) {
+            Err(DbError::SprintClosed(id)) => assert_eq!(id, s1),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let tasks = get_sprint_tasks(&db, s2, &SortSpec::open_default()).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].0.id, t2);
+        remove_sprint_task(&db, s2, t2).unwrap();
+        assert!(get_sprint_tasks(&db, s2, &SortSpec::open_default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(get_sprints(&db).unwrap().len(), 2);
+        // 66 bytes, the 64th is inside a character
+        let name = "日本語".repeat(7) + "日";
+        let s3 = add_sprint(
+            &db,
+            &name,
+            &NaiveDate::from_ymd(2019, 6, 3),
+            &NaiveDate::from_ymd(2019, 6, 14),
+            10,
+        )
+        .unwrap();
+        assert_eq!(get_sprint(&db, s3).unwrap().name, "日本語".repeat(7));
+    }
+
+    #[test]
+    fn test_get_done_tasks_range() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let dates = [
+            "2019-04-30 23:59:59",
+            "2019-05-01 00:00:00",
+            "2019-05-31 12:00:00",
+        ];
+        for date in &dates {
+            let id = add_task(&db, "done task").unwrap();
+            set_status(&db, id, "done").unwrap();
+            db.execute(
+                "UPDATE todos SET completion_date = ?1 WHERE id = ?2;",
+                params![date, &id],
+            )
+            .unwrap();
+        }
+        let may = DateRange {
+            start: Some(NaiveDate::from_ymd(2019, 5, 1).and_hms(0, 0, 0)),
+            end: Some(NaiveDate::from_ymd(2019, 6, 1).and_hms(0, 0, 0)),
+        };
+        let filter: Expr = "id>1".parse().unwrap();
+        let sort = SortSpec::done_default();
+        let ids = |tasks: Vec<task::TaskDone>| tasks.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(get_done_tasks_where(&db, None, &may, &sort).unwrap()),
+            vec![2, 3]
+        );
+        assert_eq!(
+            ids(get_done_tasks_where(&db, Some(&filter), &may, &sort).unwrap()),
+            vec![2, 3]
+        );
+        let until_may = DateRange {
+            start: None,
+            end: may.start,
+        };
+        assert_eq!(
+            ids(get_done_tasks_where(&db, Some(&filter), &until_may, &sort).unwrap()),
+            Vec::<u32>::new()
+        );
+        assert_eq!(ids(get_done_tasks(&db).unwrap()), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_get_open_tasks_where() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let t1 = add_task(&db, "backend 100% task").unwrap();
+        set_priority(&db, t1, "high").unwrap();
+        set_storypoint(&db, t1, 5).unwrap();
+        add_labels(&db, t1, &["area:backend".to_string()]).unwrap();
+        set_assignee(&db, t1, Some("alice")).unwrap();
+        let t2 = add_task(&db, "infra task").unwrap();
+        set_priority(&db, t2, "urgent").unwrap();
+        set_status(&db, t2, "block").unwrap();
+        add_labels(&db, t2, &["infra".to_string()]).unwrap();
+        let t3 = add_task(&db, "low task").unwrap();
+        set_priority(&db, t3, "low").unwrap();
+        let filters = vec![
+            "priority>=high",
+            "label:area:* or label:infra",
+            "not status:block and sp>3",
+            "priority<normal",
+            "descr:\"100%\"",
+            "descr:TASK and not label:infra",
+            "assignee:alice",
+            "assignee!=alice",
+            "id>=2 and id<3",
+        ];
+        let all_tasks = get_open_tasks(&db).unwrap();
+        for f in filters {
+            let expr: Expr = f.parse().unwrap();
+            let mut from_sql: Vec<u32> =
+                get_open_tasks_where(&db, Some(&expr), &SortSpec::open_default())
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.id)
+                    .collect();
+            from_sql.sort();
+            let mut from_eval: Vec<u32> = all_tasks
+                .iter()
+                .filter(|t| expr.eval(t, &get_labels(&db, t.id).unwrap()))
+                .map(|t| t.id)
+                .collect();
+            from_eval.sort();
+            assert_eq!(from_sql, from_eval, "filter {}", f);
+        }
+        let expr: Expr = "priority>=high and not status:block".parse().unwrap();
+        let tasks = get_open_tasks_where(&db, Some(&expr), &SortSpec::open_default()).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, t1);
+    }
+
+    #[test]
+    fn test_get_open_tasks_sorted() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        init(dbfile.path(), true).unwrap();
+        let db = get_db(dbfile.path()).unwrap();
+        let t1 = add_task(&db, "one").unwrap();
+        set_storypoint(&db, t1, 2).unwrap();
+        add_labels(&db, t1, &["b".to_string()]).unwrap();
+        let t2 = add_task(&db, "two").unwrap();
+        set_priority(&db, t2, "urgent").unwrap();
+        set_due_date(&db, t2, Some(&NaiveDate::from_ymd(2019, 6, 1))).unwrap();
+        add_labels(&db, t2, &["a".to_string()]).unwrap();
+        let t3 = add_task(&db, "three").unwrap();
+        set_storypoint(&db, t3, 8).unwrap();
+        set_due_date(&db, t3, Some(&NaiveDate::from_ymd(2019, 5, 1))).unwrap();
+        let ids = |spec: &str| -> Vec<u32> {
+            get_open_tasks_where(&db, None, &spec.parse().unwrap())
+                .unwrap()
+                .iter()
+                .map(|t| t.id)
+                .collect()
+        };
+        assert_eq!(ids("priority"), vec![t2, t1, t3]);
+        assert_eq!(ids("id:desc"), vec![t3, t2, t1]);
+        assert_eq!(ids("sp:desc,id"), vec![t3, t1, t2]);
+        assert_eq!(ids("due"), vec![t3, t2, t1]);
+        assert_eq!(ids("label"), vec![t2, t1, t3]);
+        assert_eq!(ids("priority,sp:desc"), vec![t2, t3, t1]);
+        assert_eq!(
+            get_open_tasks(&db).unwrap()[0].due_date,
+            Some("2019-06-01".to_string())
+        );
+    }
 }
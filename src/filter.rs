@@ -0,0 +1,553 @@
+//! Filter expressions for the show commands
+//!
+//! A filter is a boolean expression of comparisons, e.g.
+//! `priority>=high and (label:backend or label:infra) and not status:block and sp>3`
+//!
+//! Available fields:
+//! - `id`: the task id
+//! - `priority` (or `prio`): urgent, high, normal, low, miserable (urgent is the highest)
+//! - `status`: todo, in_progress, done, block
+//! - `label`: a label, `ns:*` selects all labels of the namespace `ns`
+//! - `sp` (or `storypoints`): the story points
+//! - `descr`: the description contains the text (case insensitive)
+//! - `assignee`: the assignee of the task
+//!
+//! Comparison operators are `:` (same as `=`), `=`, `!=`, `<`, `<=`, `>`, `>=`;
+//! ordering operators are accepted only by `id`, `priority` and `sp`.
+//! Values with spaces can be quoted with `"`.
+use crate::task::{label_match, Task};
+use failure::Fail;
+use rusqlite::types::Value;
+use std::str::FromStr;
+
+const PRIORITIES: [&str; 5] = ["urgent", "high", "normal", "low", "miserable"];
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum FilterError {
+    #[fail(display = "Invalid filter at position {}: {}", _0, _1)]
+    Parse(usize, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Id,
+    Priority,
+    Status,
+    Label,
+    StoryPoints,
+    Descr,
+    Assignee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Number(u32),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Cmp(Field, Op, Operand),
+}
+
+impl Op {
+    fn matches<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Op::Eq => left == right,
+            Op::Ne => left != right,
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+    fn as_sql(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "<>",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+    /// The priority with the highest rank has the lowest id
+    fn reversed(self) -> Self {
+        match self {
+            Op::Lt => Op::Gt,
+            Op::Le => Op::Ge,
+            Op::Gt => Op::Lt,
+            Op::Ge => Op::Le,
+            op => op,
+        }
+    }
+}
+
+/// The priority id, as in the priority table (urgent is 1)
+fn priority_id(priority: &str) -> Option<u32> {
+    PRIORITIES
+        .iter()
+        .position(|p| *p == priority)
+        .map(|i| i as u32 + 1)
+}
+
+impl Expr {
+    /// Evaluate the expression on a task and its labels
+    pub fn eval(&self, task: &Task, labels: &[String]) -> bool {
+        match self {
+            Expr::And(l, r) => l.eval(task, labels) && r.eval(task, labels),
+            Expr::Or(l, r) => l.eval(task, labels) || r.eval(task, labels),
+            Expr::Not(e) => !e.eval(task, labels),
+            Expr::Cmp(field, op, operand) => match (field, operand) {
+                (Field::Id, Operand::Number(n)) => op.matches(task.id, *n),
+                (Field::StoryPoints, Operand::Number(n)) => op.matches(task.storypoints, *n),
+                (Field::Priority, Operand::Number(n)) => {
+                    let id = priority_id(&task.priority).unwrap_or(0);
+                    op.reversed().matches(id, *n)
+                }
+                (Field::Status, Operand::Text(s)) => op.matches(task.status.as_str(), s),
+                (Field::Label, Operand::Text(s)) => {
+                    op.matches(labels.iter().any(|l| label_match(s, l)), true)
+                }
+                (Field::Descr, Operand::Text(s)) => {
+                    op.matches(task.descr.to_lowercase().contains(&s.to_lowercase()), true)
+                }
+                (Field::Assignee, Operand::Text(s)) => {
+                    op.matches(task.assignee.as_deref() == Some(s.as_str()), true)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Compile the expression into a SQL condition
+    /// The condition refers to the tables todos (t), status (s) and todo_assignee (a)
+    /// The values are appended to `values`, numbered placeholders are used
+    pub fn to_sql(&self, values: &mut Vec<Value>) -> String {
+        match self {
+            Expr::And(l, r) => format!("({} AND {})", l.to_sql(values), r.to_sql(values)),
+            Expr::Or(l, r) => format!("({} OR {})", l.to_sql(values), r.to_sql(values)),
+            Expr::Not(e) => format!("(NOT {})", e.to_sql(values)),
+            Expr::Cmp(field, op, operand) => {
+                values.push(match operand {
+                    Operand::Number(n) => Value::Integer(i64::from(*n)),
+                    Operand::Text(s) if *field == Field::Descr => {
+                        Value::Text(format!("%{}%", like_escape(s)))
+                    }
+                    Operand::Text(s) if *field == Field::Label && s.ends_with(":*") => {
+                        Value::Text(s[..s.len() - 1].to_string())
+                    }
+                    Operand::Text(s) => Value::Text(s.clone()),
+                });
+                let p = format!("?{}", values.len());
+                let negate = if *op == Op::Ne { "NOT " } else { "" };
+                match field {
+                    Field::Id => format!("t.id {} {}", op.as_sql(), p),
+                    Field::StoryPoints => {
+                        format!("COALESCE(t.story_points, 0) {} {}", op.as_sql(), p)
+                    }
+                    Field::Priority => format!("t.priority_id {} {}", op.reversed().as_sql(), p),
+                    Field::Status => format!("s.descr {} {}", op.as_sql(), p),
+                    Field::Descr => format!("t.descr {}LIKE {} ESCAPE '\\'", negate, p),
+                    Field::Assignee => {
+                        let expected = if *op == Op::Ne { 0 } else { 1 };
+                        format!("COALESCE(a.assignee = {}, 0) = {}", p, expected)
+                    }
+                    Field::Label => {
+                        // a prefix match, case sensitive as label_match (LIKE is not)
+                        let cmp = match operand {
+                            Operand::Text(s) if s.ends_with(":*") => {
+                                format!("substr(l.label, 1, {}) = {}", s.chars().count() - 1, p)
+                            }
+                            _ => format!("l.label = {}", p),
+                        };
+                        format!(
+                            "{}EXISTS (SELECT 1 FROM todo_label l WHERE l.todo_id = t.id AND {})",
+                            negate, cmp
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn and(self, other: Expr) -> Expr {
+        Expr::And(Box::new(self), Box::new(other))
+    }
+}
+
+fn like_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+impl FromStr for Expr {
+    type Err = FilterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input: s.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        parser.skip_whitespaces();
+        if parser.pos < parser.input.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+        Ok(expr)
+    }
+}
+
+struct Parser {
+    input: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, msg: &str) -> FilterError {
+        FilterError::Parse(self.pos, msg.to_string())
+    }
+
+    fn skip_whitespaces(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).cloned()
+    }
+
+    /// Consume the keyword, if it's the next word in the input
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespaces();
+        let end = self.pos + keyword.len();
+        if end > self.input.len() {
+            return false;
+        }
+        let word: String = self.input[self.pos..end].iter().collect();
+        let boundary = self
+            .input
+            .get(end)
+            .map_or(true, |c| c.is_whitespace() || *c == '(' || *c == ')');
+        if boundary && word.eq_ignore_ascii_case(keyword) {
+            self.pos = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut left = self.parse_and()?;
+        while self.keyword("or") {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut left = self.parse_not()?;
+        while self.keyword("and") {
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, FilterError> {
+        if self.keyword("not") {
+            Ok(Expr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, FilterError> {
+        self.skip_whitespaces();
+        if self.peek() == Some('(') {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            self.skip_whitespaces();
+            if self.peek() != Some(')') {
+                return Err(self.error("expected )"));
+            }
+            self.pos += 1;
+            Ok(expr)
+        } else {
+            self.parse_comparison()
+        }
+    }
+
+    fn parse_field(&mut self) -> Result<Field, FilterError> {
+        let start = self.pos;
+        while self
+            .peek()
+            .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        {
+            self.pos += 1;
+        }
+        let name: String = self.input[start..self.pos].iter().collect();
+        match name.to_lowercase().as_str() {
+            "id" => Ok(Field::Id),
+            "priority" | "prio" => Ok(Field::Priority),
+            "status" => Ok(Field::Status),
+            "label" => Ok(Field::Label),
+            "sp" | "storypoints" => Ok(Field::StoryPoints),
+            "descr" => Ok(Field::Descr),
+            "assignee" => Ok(Field::Assignee),
+            "" => {
+                self.pos = start;
+                Err(self.error("expected a field"))
+            }
+            _ => {
+                self.pos = start;
+                Err(self.error(&format!("unknown field {}", name)))
+            }
+        }
+    }
+
+    fn parse_op(&mut self) -> Result<Op, FilterError> {
+        self.skip_whitespaces();
+        let next = self.input.get(self.pos + 1).cloned();
+        let (op, len) = match (self.peek(), next) {
+            (Some('<'), Some('=')) => (Op::Le, 2),
+            (Some('>'), Some('=')) => (Op::Ge, 2),
+            (Some('!'), Some('=')) => (Op::Ne, 2),
+            (Some('<'), _) => (Op::Lt, 1),
+            (Some('>'), _) => (Op::Gt, 1),
+            (Some('='), _) | (Some(':'), _) => (Op::Eq, 1),
+            _ => return Err(self.error("expected an operator")),
+        };
+        self.pos += len;
+        Ok(op)
+    }
+
+    fn parse_value(&mut self) -> Result<String, FilterError> {
+        self.skip_whitespaces();
+        let mut value = String::new();
+        if self.peek() == Some('"') {
+            self.pos += 1;
+            loop {
+                match self.peek() {
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(self.error("unterminated quoted value")),
+                }
+                self.pos += 1;
+            }
+            self.pos += 1;
+        } else {
+            while let Some(c) = self.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' {
+                    break;
+                }
+                value.push(c);
+                self.pos += 1;
+            }
+            if value.is_empty() {
+                return Err(self.error("expected a value"));
+            }
+        }
+        Ok(value)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, FilterError> {
+        let field = self.parse_field()?;
+        let op = self.parse_op()?;
+        let value_pos = self.pos;
+        let value = self.parse_value()?;
+        let value_error = |msg: String| FilterError::Parse(value_pos, msg);
+        let operand = match field {
+            Field::Id | Field::StoryPoints => Operand::Number(
+                value
+                    .parse()
+                    .map_err(|_| value_error(format!("{} is not a number", value)))?,
+            ),
+            Field::Priority => Operand::Number(
+                priority_id(&value)
+                    .ok_or_else(|| value_error(format!("{} is not a priority", value)))?,
+            ),
+            _ => {
+                if op != Op::Eq && op != Op::Ne {
+                    return Err(value_error("only = and != are allowed".to_string()));
+                }
+                Operand::Text(value)
+            }
+        };
+        Ok(Expr::Cmp(field, op, operand))
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+    use crate::db;
+    use crate::sort::SortSpec;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn task(priority: &str, status: &str, storypoints: u32) -> Task {
+        Task {
+            id: 1,
+            descr: "Fix the Parser".to_string(),
+            priority: priority.to_string(),
+            status: status.to_string(),
+            storypoints,
+            assignee: Some("alice".to_string()),
+            due_date: None,
+            creation_date: "2019-06-01 10:00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let uut: Expr = "sp>3".parse().unwrap();
+        assert_eq!(
+            uut,
+            Expr::Cmp(Field::StoryPoints, Op::Gt, Operand::Number(3))
+        );
+        let uut: Expr = "not status:block and label:a or label:b".parse().unwrap();
+        assert_eq!(
+            uut,
+            Expr::Or(
+                Box::new(Expr::And(
+                    Box::new(Expr::Not(Box::new(Expr::Cmp(
+                        Field::Status,
+                        Op::Eq,
+                        Operand::Text("block".to_string())
+                    )))),
+                    Box::new(Expr::Cmp(
+                        Field::Label,
+                        Op::Eq,
+                        Operand::Text("a".to_string())
+                    ))
+                )),
+                Box::new(Expr::Cmp(
+                    Field::Label,
+                    Op::Eq,
+                    Operand::Text("b".to_string())
+                ))
+            )
+        );
+        let uut: Expr = "label:area:* and descr = \"the parser\"".parse().unwrap();
+        assert_eq!(
+            uut,
+            Expr::And(
+                Box::new(Expr::Cmp(
+                    Field::Label,
+                    Op::Eq,
+                    Operand::Text("area:*".to_string())
+                )),
+                Box::new(Expr::Cmp(
+                    Field::Descr,
+                    Op::Eq,
+                    Operand::Text("the parser".to_string())
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("".parse::<Expr>().is_err());
+        assert!("foo:bar".parse::<Expr>().is_err());
+        assert!("sp>three".parse::<Expr>().is_err());
+        assert!("priority>=highest".parse::<Expr>().is_err());
+        assert!("label>a".parse::<Expr>().is_err());
+        assert!("(sp>3".parse::<Expr>().is_err());
+        assert!("sp>3 sp<5".parse::<Expr>().is_err());
+        assert_eq!(
+            "sp>3 and".parse::<Expr>(),
+            Err(FilterError::Parse(8, "expected a field".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_eval() {
+        let labels = vec!["area:backend".to_string(), "infra".to_string()];
+        let uut: Expr =
+            "priority>=high and (label:backend or label:infra) and not status:block and sp>3"
+                .parse()
+                .unwrap();
+        assert!(uut.eval(&task("urgent", "todo", 5), &labels));
+        assert!(uut.eval(&task("high", "todo", 5), &labels));
+        assert!(!uut.eval(&task("normal", "todo", 5), &labels));
+        assert!(!uut.eval(&task("high", "block", 5), &labels));
+        assert!(!uut.eval(&task("high", "todo", 3), &labels));
+        assert!(!uut.eval(&task("high", "todo", 5), &[]));
+        let uut: Expr = "label:area:* and descr:parser and assignee:alice"
+            .parse()
+            .unwrap();
+        assert!(uut.eval(&task("low", "todo", 0), &labels));
+        let uut: Expr = "label!=infra or assignee!=alice".parse().unwrap();
+        assert!(!uut.eval(&task("low", "todo", 0), &labels));
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let uut: Expr = "priority>=high and not label:area:*".parse().unwrap();
+        let mut values = Vec::new();
+        assert_eq!(
+            uut.to_sql(&mut values),
+            "(t.priority_id <= ?1 AND (NOT EXISTS (SELECT 1 FROM todo_label l WHERE l.todo_id = t.id AND substr(l.label, 1, 5) = ?2)))"
+        );
+        assert_eq!(
+            values,
+            vec![Value::Integer(2), Value::Text("area:".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_to_sql_eval_labels() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let db = db::get_db(dbfile.path()).unwrap();
+        for labels in &[
+            &["area:web"][..],
+            &["Area:web"],
+            &["AREA:db", "area"],
+            &["area_x:y"],
+        ] {
+            let id = db::add_task(&db, "task").unwrap();
+            let labels: Vec<String> = labels.iter().map(|l| l.to_string()).collect();
+            db::add_labels(&db, id, &labels).unwrap();
+        }
+        for filter in &[
+            "label:area:*",
+            "label:Area:*",
+            "label:area",
+            "not label:AREA:*",
+        ] {
+            let uut: Expr = filter.parse().unwrap();
+            let sql: Vec<u32> =
+                db::get_open_tasks_where(&db, Some(&uut), &SortSpec::open_default())
+                    .unwrap()
+                    .iter()
+                    .map(|t| t.id)
+                    .collect();
+            let eval: Vec<u32> = db::get_open_tasks(&db)
+                .unwrap()
+                .iter()
+                .filter(|t| uut.eval(t, &db::get_labels(&db, t.id).unwrap()))
+                .map(|t| t.id)
+                .collect();
+            assert_eq!(sql, eval, "filter {}", filter);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Complete tasks from the commit messages of a local git repository
+//!
+//! A commit closes a task with a `Closes-Task: 42` trailer or a `myrello #42`
+//! mention, a `Refs: 42` trailer only attaches the commit to the task. The
+//! commit hash is added to the references of the task, so a commit already
+//! seen is skipped and the sync can run again on the same commits
+use crate::db;
+use failure::Fail;
+use rusqlite::{Connection, Error};
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Fail, Debug)]
+pub enum GitSyncError {
+    #[fail(display = "Failed to run git: {}", _0)]
+    Io(#[cause] std::io::Error),
+    #[fail(display = "git log failed: {}", _0)]
+    Git(String),
+    #[fail(display = "Database error: {}", _0)]
+    Db(#[cause] Error),
+}
+
+impl From<std::io::Error> for GitSyncError {
+    fn from(e: std::io::Error) -> Self {
+        GitSyncError::Io(e)
+    }
+}
+
+impl From<Error> for GitSyncError {
+    fn from(e: Error) -> Self {
+        GitSyncError::Db(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub hash: String,
+    pub message: String,
+}
+
+impl Commit {
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("").trim()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Complete the task
+    Close,
+    /// Only attach the commit to the task
+    Refs,
+}
+
+/// What the sync did with a task mentioned by a commit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Completed,
+    Referenced,
+    /// The commit is already in the references of the task
+    AlreadySynced,
+    UnknownTask,
+}
+
+/// The commits of the repository, the oldest first
+/// With since, only the commits after it (since..HEAD)
+pub fn log(repo: &Path, since: Option<&str>) -> Result<Vec<Commit>, GitSyncError> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C")
+        .arg(repo)
+        .args(["log", "--reverse", "--format=%H%x1f%B%x1e"]);
+    if let Some(since) = since {
+        cmd.arg(format!("{}..HEAD", since));
+    }
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(GitSyncError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start().splitn(2, '\x1f');
+            match (fields.next(), fields.next()) {
+                (Some(hash), Some(message)) if !hash.is_empty() => Some(Commit {
+                    hash: hash.to_string(),
+                    message: message.trim().to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+/// The task ids in a list as `42, #43`
+fn parse_ids(value: &str) -> Vec<u32> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|v| v.trim_start_matches('#').parse().ok())
+        .collect()
+}
+
+/// The tasks mentioned in a commit message, with the action to take
+/// If a task is both closed and referenced, it's closed
+pub fn parse_message(message: &str) -> Vec<(u32, Action)> {
+    let mut rv: Vec<(u32, Action)> = Vec::new();
+    let mut add = |id: u32, action: Action| match rv.iter_mut().find(|(i, _)| *i == id) {
+        Some(found) if action == Action::Close => found.1 = Action::Close,
+        Some(_) => {}
+        None => rv.push((id, action)),
+    };
+    for line in message.lines() {
+        if let Some(i) = line.find(':') {
+            let key = line[..i].trim();
+            if key.eq_ignore_ascii_case("closes-task") {
+                parse_ids(&line[i + 1..])
+                    .into_iter()
+                    .for_each(|id| add(id, Action::Close));
+            } else if key.eq_ignore_ascii_case("refs") {
+                parse_ids(&line[i + 1..])
+                    .into_iter()
+                    .for_each(|id| add(id, Action::Refs));
+            }
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        for pair in words.windows(2) {
+            if pair[0].eq_ignore_ascii_case("myrello") && pair[1].starts_with('#') {
+                let id = pair[1][1..].trim_end_matches(|c: char| !c.is_ascii_digit());
+                if let Ok(id) = id.parse() {
+                    add(id, Action::Close);
+                }
+            }
+        }
+    }
+    rv
+}
+
+/// The reference line of a commit
+fn commit_reference(commit: &Commit) -> String {
+    format!("commit {} {}", commit.hash, commit.subject())
+}
+
+/// Apply the action of a commit to a task
+pub fn sync_task(
+    db: &Connection,
+    commit: &Commit,
+    id: u32,
+    action: Action,
+) -> Result<Outcome, Error> {
+    let status = match db::get_task_status(db, id) {
+        Ok(status) => status,
+        Err(Error::QueryReturnedNoRows) => return Ok(Outcome::UnknownTask),
+        Err(e) => return Err(e),
+    };
+    let refs = db::get_refs(db, id)?;
+    if refs.contains(&commit.hash) {
+        return Ok(Outcome::AlreadySynced);
+    }
+    let reference = commit_reference(commit);
+    if refs.trim().is_empty() {
+        db::set_reference(db, id, &reference)?;
+    } else {
+        db::set_reference(db, id, &format!("{}\n{}", refs.trim_end(), reference))?;
+    }
+    if action == Action::Close && status != "done" {
+        db::complete_task(db, id)?;
+        db::set_status(db, id, "done")?;
+        db::complete_steps(db, id)?;
+        Ok(Outcome::Completed)
+    } else {
+        Ok(Outcome::Referenced)
+    }
+}
+
+/// Apply the commits, in order, to the tasks they mention
+pub fn sync(db: &Connection, commits: &[Commit]) -> Result<Vec<(Commit, u32, Outcome)>, Error> {
+    let mut rv = Vec::new();
+    for c in commits {
+        for (id, action) in parse_message(&c.message) {
+            let outcome = sync_task(db, c, id, action)?;
+            rv.push((c.clone(), id, outcome));
+        }
+    }
+    Ok(rv)
+}
+
+#[cfg(test)]
+mod gitsync_tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(parse_message("fix the build"), vec![]);
+        assert_eq!(
+            parse_message(
+                "fix the build, see myrello #3.\n\nRefs: #4, 5\nCloses-Task: 42\nrefs: 3\nRefs: https://x.org"
+            ),
+            vec![(3, Action::Close), (4, Action::Refs), (5, Action::Refs), (42, Action::Close)]
+        );
+        assert_eq!(
+            parse_message("Refs: 7\ncloses-task: #7"),
+            vec![(7, Action::Close)]
+        );
+    }
+
+    #[test]
+    fn test_sync() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let db = db::get_db(dbfile.path()).unwrap();
+        for descr in &["first", "second"] {
+            let id = db::add_task(&db, descr).unwrap();
+            db::add_step(&db, id, "start").unwrap();
+        }
+        db::set_reference(&db, 2, "ticket 12").unwrap();
+        let commits = vec![
+            Commit {
+                hash: "a".repeat(40),
+                message: "add the feature\n\nCloses-Task: 1\nRefs: 2, 9".to_string(),
+            },
+            Commit {
+                hash: "b".repeat(40),
+                message: "finish it, myrello #2".to_string(),
+            },
+        ];
+        let uut: Vec<(u32, Outcome)> = sync(&db, &commits)
+            .unwrap()
+            .into_iter()
+            .map(|(_, id, outcome)| (id, outcome))
+            .collect();
+        assert_eq!(
+            uut,
+            vec![
+                (1, Outcome::Completed),
+                (2, Outcome::Referenced),
+                (9, Outcome::UnknownTask),
+                (2, Outcome::Completed)
+            ]
+        );
+        assert_eq!(db::get_task_status(&db, 1).unwrap(), "done");
+        assert!(db::get_task_completion_date(&db, 1).unwrap().is_some());
+        assert_eq!(
+            db::get_refs(&db, 2).unwrap(),
+            format!(
+                "ticket 12\ncommit {} add the feature\ncommit {} finish it, myrello #2",
+                "a".repeat(40),
+                "b".repeat(40)
+            )
+        );
+        // a second run changes nothing
+        let uut: Vec<Outcome> = sync(&db, &commits)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, outcome)| outcome)
+            .collect();
+        assert_eq!(
+            uut,
+            vec![
+                Outcome::AlreadySynced,
+                Outcome::AlreadySynced,
+                Outcome::UnknownTask,
+                Outcome::AlreadySynced
+            ]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! User scripts run on the task events
+//!
+//! A hook is an executable in the hooks directory, named after the event:
+//! `pre-done` runs before a task is completed, `on-done` after it. The task
+//! is written as JSON on the standard input of the hook; a `pre-` hook exiting
+//! with a non-zero code aborts the operation
+use crate::op;
+use failure::Fail;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Fail, Debug)]
+pub enum HookError {
+    #[fail(display = "Failed to run the hook {}: {}", _0, _1)]
+    Io(String, #[cause] std::io::Error),
+    #[fail(display = "Failed to encode the task for the hook {}: {}", _0, _1)]
+    Json(String, #[cause] serde_json::Error),
+    #[fail(display = "The hook {} aborted the operation (exit status {})", _0, _1)]
+    Aborted(String, String),
+    #[fail(display = "The hook {} failed (exit status {})", _0, _1)]
+    Failed(String, String),
+}
+
+/// The events of a task
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Add,
+    Start,
+    Block,
+    Done,
+    Delete,
+    /// A step, by id, is completed
+    StepDone(u32),
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Add => "add",
+            Event::Start => "start",
+            Event::Block => "block",
+            Event::Done => "done",
+            Event::Delete => "delete",
+            Event::StepDone(_) => "step-done",
+        }
+    }
+}
+
+/// When the hook runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum When {
+    /// Before the operation, that is aborted if the hook fails
+    Pre,
+    /// After the operation
+    On,
+}
+
+impl When {
+    fn prefix(self) -> &'static str {
+        match self {
+            When::Pre => "pre",
+            When::On => "on",
+        }
+    }
+}
+
+/// The hooks of a directory, a missing directory means no hooks
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    dir: Option<PathBuf>,
+}
+
+impl Hooks {
+    pub fn new(dir: &Path) -> Self {
+        Hooks {
+            dir: Some(dir.to_path_buf()),
+        }
+    }
+
+    /// The hook of the event, if any
+    pub fn find(&self, when: When, event: Event) -> Option<PathBuf> {
+        let path = self
+            .dir
+            .as_ref()?
+            .join(format!("{}-{}", when.prefix(), event.name()));
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Run the hook of the event, if any, with the task on its standard input
+    /// The environment has `MYRELLO_EVENT`, `MYRELLO_TASK_ID` and, for the
+    /// steps, `MYRELLO_STEP_ID`
+    pub fn run(&self, when: When, event: Event, task: &op::Task) -> Result<(), HookError> {
+        let path = match self.find(when, event) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let json = serde_json::to_vec(task).map_err(|e| HookError::Json(name.clone(), e))?;
+        let mut cmd = Command::new(&path);
+        cmd.env("MYRELLO_EVENT", event.name())
+            .env("MYRELLO_TASK_ID", task.id.to_string())
+            .stdin(Stdio::piped());
+        if let Event::StepDone(step_id) = event {
+            cmd.env("MYRELLO_STEP_ID", step_id.to_string());
+        }
+        let mut child = cmd.spawn().map_err(|e| HookError::Io(name.clone(), e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // a hook not interested in the task may exit without reading it
+            match stdin.write_all(&json) {
+                Err(ref e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+                Err(e) => return Err(HookError::Io(name, e)),
+                Ok(_) => {}
+            }
+        }
+        let status = child.wait().map_err(|e| HookError::Io(name.clone(), e))?;
+        if status.success() {
+            return Ok(());
+        }
+        let code = status
+            .code()
+            .map_or_else(|| "killed".to_string(), |c| c.to_string());
+        match when {
+            When::Pre => Err(HookError::Aborted(name, code)),
+            When::On => Err(HookError::Failed(name, code)),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod hooks_tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn hook(temp: &TempDir, name: &str, script: &str) {
+        let file = temp.child(name);
+        file.write_str(script).unwrap();
+        std::fs::set_permissions(file.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_run() {
+        let temp = TempDir::new().unwrap();
+        let out = temp.child("out.json");
+        hook(
+            &temp,
+            "on-step-done",
+            &format!(
+                "#!/bin/sh\ncat > {0}\necho >> {0}\necho \"$MYRELLO_EVENT $MYRELLO_TASK_ID $MYRELLO_STEP_ID\" >> {0}\n",
+                out.path().display()
+            ),
+        );
+        hook(&temp, "pre-delete", "#!/bin/sh\nexit 3\n");
+        let uut = Hooks::new(temp.path());
+        let task = op::Task {
+            id: 4,
+            descr: "a task".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(uut.find(When::Pre, Event::Done), None);
+        uut.run(When::Pre, Event::Done, &task).unwrap();
+        uut.run(When::On, Event::StepDone(2), &task).unwrap();
+        let output = std::fs::read_to_string(out.path()).unwrap();
+        let mut lines = output.lines();
+        let sent: op::Task = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(sent, task);
+        assert_eq!(lines.next(), Some("step-done 4 2"));
+        match uut.run(When::Pre, Event::Delete, &task) {
+            Err(HookError::Aborted(name, code)) => {
+                assert_eq!(name, "pre-delete");
+                assert_eq!(code, "3");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Hooks::default().find(When::Pre, Event::Delete).is_none());
+    }
+}
@@ -1,7 +1,18 @@
+pub mod chart;
 pub mod cli_opt;
+pub mod config;
+pub mod daemon;
 pub mod db;
+pub mod filter;
+pub mod gitsync;
+pub mod hooks;
+pub mod metrics;
 pub mod op;
+pub mod output;
+pub mod report;
+pub mod sort;
 pub mod task;
+pub mod transfer;
 
 use rusqlite::Connection;
 use std::collections::HashSet;
@@ -0,0 +1,580 @@
+//! Reports computed from the history of the tasks
+//!
+//! The data are collected from the database first, the computation works on
+//! plain values and the rendering uses the common output formats
+use crate::db;
+use crate::filter::Expr;
+use crate::output::{self, Format};
+use crate::sort::SortSpec;
+use crate::task::{self, DateRange, ShowParams};
+use chrono::prelude::*;
+use chrono::Duration;
+use rusqlite::{Connection, Error};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use strum_macros::{Display, EnumString};
+
+/// How to group the done tasks in the velocity report
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+pub enum VelocityBy {
+    #[strum(serialize = "week")]
+    Week,
+    #[strum(serialize = "sprint")]
+    Sprint,
+}
+
+/// A completed task, as counted by the reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoneItem {
+    /// The local day of the completion
+    pub day: NaiveDate,
+    pub storypoints: u32,
+    pub labels: Vec<String>,
+}
+
+/// The completed work of a week or of a sprint
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VelocityBucket {
+    pub name: String,
+    pub tasks: u32,
+    pub storypoints: u32,
+    /// The rolling average of the story points, this bucket included
+    pub average: f64,
+    /// The story points per label; a task counts for each of its labels
+    pub labels: BTreeMap<String, u32>,
+}
+
+impl VelocityBucket {
+    fn new(name: String) -> Self {
+        VelocityBucket {
+            name,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, storypoints: u32, labels: &[String]) {
+        self.tasks += 1;
+        self.storypoints += storypoints;
+        for l in labels {
+            *self.labels.entry(l.clone()).or_insert(0) += storypoints;
+        }
+    }
+}
+
+/// The Monday of the week of `day`
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(i64::from(day.weekday().num_days_from_monday()))
+}
+
+/// Group the items in `weeks` weeks, starting from the week of `first`
+/// Weeks without completed tasks are kept, with zero story points
+pub fn velocity_by_week(items: &[DoneItem], first: NaiveDate, weeks: u32) -> Vec<VelocityBucket> {
+    let first = week_start(first);
+    let mut buckets: Vec<VelocityBucket> = (0..weeks)
+        .map(|w| VelocityBucket::new((first + Duration::weeks(i64::from(w))).to_string()))
+        .collect();
+    for item in items {
+        let week = (item.day - first).num_days().div_euclid(7);
+        if week >= 0 && week < i64::from(weeks) {
+            buckets[week as usize].add(item.storypoints, &item.labels);
+        }
+    }
+    buckets
+}
+
+/// Set the rolling average of the story points over `window` buckets
+/// The first buckets average over the buckets available
+pub fn set_rolling_average(buckets: &mut [VelocityBucket], window: usize) {
+    let window = window.max(1);
+    let points: Vec<u32> = buckets.iter().map(|b| b.storypoints).collect();
+    for (i, bucket) in buckets.iter_mut().enumerate() {
+        let values = &points[(i + 1).saturating_sub(window)..=i];
+        bucket.average = f64::from(values.iter().sum::<u32>()) / values.len() as f64;
+    }
+}
+
+/// The local day of a date stored in the database (UTC)
+pub fn local_day(date: &str) -> Option<NaiveDate> {
+    let utc = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok()?;
+    Some(
+        Utc.from_utc_datetime(&utc)
+            .with_timezone(&Local)
+            .naive_local()
+            .date(),
+    )
+}
+
+/// The velocity of the last `weeks` weeks, the current one included
+pub fn velocity_weeks(
+    db: &Connection,
+    filter: Option<&Expr>,
+    param: &ShowParams,
+    weeks: u32,
+) -> Result<Vec<VelocityBucket>, Error> {
+    let first = week_start(Local::today().naive_local()) - Duration::weeks(i64::from(weeks) - 1);
+    let range = DateRange::from_local_days(Some(first), None);
+    let tasks = db::get_done_tasks_where(db, filter, &range, &SortSpec::done_default())?;
+    let mut items = Vec::new();
+    for t in tasks {
+        let labels = db::get_labels(db, t.id)?;
+        if task::check_label(param.label, &labels)
+            && task::check_assignee(param.assignee, &t.assignee)
+        {
+            if let Some(day) = local_day(&t.completion_date) {
+                items.push(DoneItem {
+                    day,
+                    storypoints: t.storypoints,
+                    labels,
+                });
+            }
+        }
+    }
+    Ok(velocity_by_week(&items, first, weeks))
+}
+
+/// The velocity of the last `sprints` sprints already started
+/// Only the tasks completed within the sprint are counted
+pub fn velocity_sprints(
+    db: &Connection,
+    filter: Option<&Expr>,
+    param: &ShowParams,
+    sprints: u32,
+) -> Result<Vec<VelocityBucket>, Error> {
+    let today = Local::today().naive_local().to_string();
+    let started: Vec<task::Sprint> = db::get_sprints(db)?
+        .into_iter()
+        .filter(|s| s.start_date <= today)
+        .collect();
+    let skip = started.len().saturating_sub(sprints as usize);
+    let mut buckets = Vec::new();
+    for sprint in started.into_iter().skip(skip) {
+        let mut bucket = VelocityBucket::new(format!("{} {}", sprint.id, sprint.name));
+        for (t, done) in db::get_sprint_tasks(db, sprint.id, &SortSpec::open_default())? {
+            let labels = db::get_labels(db, t.id)?;
+            if done
+                && task::check_label(param.label, &labels)
+                && task::check_assignee(param.assignee, &t.assignee)
+                && filter.map_or(true, |f| f.eval(&t, &labels))
+            {
+                bucket.add(t.storypoints, &labels);
+            }
+        }
+        buckets.push(bucket);
+    }
+    Ok(buckets)
+}
+
+/// The lead time and the cycle time of a done task, in days
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlowItem {
+    pub id: u32,
+    pub descr: String,
+    pub priority: String,
+    pub labels: Vec<String>,
+    /// From the creation to the completion
+    pub lead_time: f64,
+    /// From the start (step 0) to the completion, if the task was started
+    pub cycle_time: Option<f64>,
+}
+
+/// The percentiles 50, 85 and 95 of a set of values
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p85: f64,
+    pub p95: f64,
+}
+
+/// The flow metrics of a group of tasks: all, a priority or a label
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlowGroup {
+    pub group: String,
+    pub tasks: usize,
+    pub lead_time: Option<Percentiles>,
+    pub cycle_time: Option<Percentiles>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FlowReport {
+    pub groups: Vec<FlowGroup>,
+    /// The tasks with the longest lead time
+    pub slowest: Vec<FlowItem>,
+}
+
+/// The percentile `p` of the sorted values, with the nearest rank method
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1) - 1]
+}
+
+fn percentiles(mut values: Vec<f64>) -> Option<Percentiles> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Some(Percentiles {
+        p50: percentile(&values, 50.0),
+        p85: percentile(&values, 85.0),
+        p95: percentile(&values, 95.0),
+    })
+}
+
+fn flow_group(group: String, items: &[&FlowItem]) -> FlowGroup {
+    FlowGroup {
+        group,
+        tasks: items.len(),
+        lead_time: percentiles(items.iter().map(|i| i.lead_time).collect()),
+        cycle_time: percentiles(items.iter().filter_map(|i| i.cycle_time).collect()),
+    }
+}
+
+/// Compute the flow metrics of all the items, per priority and per label
+/// The `slowest` items by lead time are listed as well
+pub fn flow_report(items: &[FlowItem], slowest: usize) -> FlowReport {
+    let mut groups = vec![flow_group(
+        "all".to_string(),
+        &items.iter().collect::<Vec<_>>(),
+    )];
+    let mut priorities: BTreeMap<&str, Vec<&FlowItem>> = BTreeMap::new();
+    let mut labels: BTreeMap<&str, Vec<&FlowItem>> = BTreeMap::new();
+    for item in items {
+        priorities.entry(&item.priority).or_default().push(item);
+        for l in &item.labels {
+            labels.entry(l).or_default().push(item);
+        }
+    }
+    groups.extend(
+        priorities
+            .iter()
+            .map(|(p, items)| flow_group(format!("priority: {}", p), items)),
+    );
+    groups.extend(
+        labels
+            .iter()
+            .map(|(l, items)| flow_group(format!("label: {}", l), items)),
+    );
+    let mut sorted = items.to_vec();
+    sorted.sort_by(|a, b| {
+        b.lead_time
+            .partial_cmp(&a.lead_time)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    sorted.truncate(slowest);
+    FlowReport {
+        groups,
+        slowest: sorted,
+    }
+}
+
+fn days_between(from: &str, to: &str) -> Option<f64> {
+    let from = NaiveDateTime::parse_from_str(from, "%Y-%m-%d %H:%M:%S").ok()?;
+    let to = NaiveDateTime::parse_from_str(to, "%Y-%m-%d %H:%M:%S").ok()?;
+    Some((to - from).num_seconds() as f64 / 86400.0)
+}
+
+/// Collect the lead and cycle time of the tasks completed in the range
+/// A task has a cycle time only if it was started: completing a task leaves
+/// its start step (step 0) open
+pub fn flow_items(
+    db: &Connection,
+    filter: Option<&Expr>,
+    param: &ShowParams,
+    range: &DateRange,
+) -> Result<Vec<FlowItem>, Error> {
+    let tasks = db::get_done_tasks_where(db, filter, range, &SortSpec::done_default())?;
+    let mut items = Vec::new();
+    for t in tasks {
+        let labels = db::get_labels(db, t.id)?;
+        if !task::check_label(param.label, &labels)
+            || !task::check_assignee(param.assignee, &t.assignee)
+        {
+            continue;
+        }
+        if let Some(lead_time) = days_between(&t.creation_date, &t.completion_date) {
+            let cycle_time = db::get_start_date(db, t.id)?
+                .and_then(|start| days_between(&start, &t.completion_date));
+            items.push(FlowItem {
+                id: t.id,
+                descr: t.descr,
+                priority: t.priority,
+                labels,
+                lead_time,
+                cycle_time,
+            });
+        }
+    }
+    Ok(items)
+}
+
+fn days_to_str(days: Option<f64>) -> String {
+    days.map(|d| format!("{:.1}", d)).unwrap_or_default()
+}
+
+/// Print the flow metrics, followed by the slowest tasks
+/// The times are in days
+pub fn show_flow(report: &FlowReport, format: Format) {
+    if format == Format::Json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(report).expect("Failed to serialize the flow metrics")
+        );
+        return;
+    }
+    let headers: Vec<String> = [
+        "Group",
+        "Tasks",
+        "Lead p50",
+        "Lead p85",
+        "Lead p95",
+        "Cycle p50",
+        "Cycle p85",
+        "Cycle p95",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect();
+    let rows: Vec<Vec<String>> = report
+        .groups
+        .iter()
+        .map(|g| {
+            let lead = g.lead_time.as_ref();
+            let cycle = g.cycle_time.as_ref();
+            vec![
+                g.group.clone(),
+                g.tasks.to_string(),
+                days_to_str(lead.map(|p| p.p50)),
+                days_to_str(lead.map(|p| p.p85)),
+                days_to_str(lead.map(|p| p.p95)),
+                days_to_str(cycle.map(|p| p.p50)),
+                days_to_str(cycle.map(|p| p.p85)),
+                days_to_str(cycle.map(|p| p.p95)),
+            ]
+        })
+        .collect();
+    output::print_report(format, &headers, &rows);
+    if report.slowest.is_empty() {
+        return;
+    }
+    println!();
+    let headers: Vec<String> = ["Id", "Description", "Priority", "Lead time", "Cycle time"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+    let rows: Vec<Vec<String>> = report
+        .slowest
+        .iter()
+        .map(|i| {
+            vec![
+                i.id.to_string(),
+                i.descr.clone(),
+                i.priority.clone(),
+                days_to_str(Some(i.lead_time)),
+                days_to_str(i.cycle_time),
+            ]
+        })
+        .collect();
+    output::print_report(format, &headers, &rows);
+}
+
+/// Print the velocity report, with a column per label if `by_label`
+pub fn show_velocity(buckets: &[VelocityBucket], by: VelocityBy, by_label: bool, format: Format) {
+    if format == Format::Json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(buckets).expect("Failed to serialize the velocity")
+        );
+        return;
+    }
+    let labels: BTreeSet<&String> = if by_label {
+        buckets.iter().flat_map(|b| b.labels.keys()).collect()
+    } else {
+        BTreeSet::new()
+    };
+    let mut headers: Vec<String> = vec![
+        match by {
+            VelocityBy::Week => "Week",
+            VelocityBy::Sprint => "Sprint",
+        }
+        .to_string(),
+        "Tasks".to_string(),
+        "Story points".to_string(),
+        "Average".to_string(),
+    ];
+    headers.extend(labels.iter().map(|l| l.to_string()));
+    let rows: Vec<Vec<String>> = buckets
+        .iter()
+        .map(|b| {
+            let mut row = vec![
+                b.name.clone(),
+                b.tasks.to_string(),
+                b.storypoints.to_string(),
+                format!("{:.1}", b.average),
+            ];
+            row.extend(
+                labels
+                    .iter()
+                    .map(|l| b.labels.get(*l).unwrap_or(&0).to_string()),
+            );
+            row
+        })
+        .collect();
+    output::print_report(format, &headers, &rows);
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn item(day: NaiveDate, storypoints: u32, labels: &[&str]) -> DoneItem {
+        DoneItem {
+            day,
+            storypoints,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_velocity_by_week() {
+        let d = |m, d| NaiveDate::from_ymd(2019, m, d);
+        let items = vec![
+            item(d(5, 6), 3, &["bug"]),
+            item(d(5, 12), 2, &["bug", "area:ui"]),
+            item(d(5, 20), 5, &[]),
+            // before and after the weeks
+            item(d(5, 5), 8, &[]),
+            item(d(5, 27), 8, &[]),
+        ];
+        let mut uut = velocity_by_week(&items, d(5, 8), 3);
+        assert_eq!(uut.len(), 3);
+        assert_eq!(uut[0].name, "2019-05-06");
+        assert_eq!((uut[0].tasks, uut[0].storypoints), (2, 5));
+        assert_eq!(uut[0].labels.get("bug"), Some(&5));
+        assert_eq!(uut[0].labels.get("area:ui"), Some(&2));
+        assert_eq!((uut[1].tasks, uut[1].storypoints), (0, 0));
+        assert_eq!((uut[2].tasks, uut[2].storypoints), (1, 5));
+        set_rolling_average(&mut uut, 2);
+        let averages: Vec<f64> = uut.iter().map(|b| b.average).collect();
+        assert_eq!(averages, vec![5.0, 2.5, 2.5]);
+    }
+
+    fn flow_item(id: u32, priority: &str, labels: &[&str], lead_time: f64) -> FlowItem {
+        FlowItem {
+            id,
+            priority: priority.to_string(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            lead_time,
+            cycle_time: if lead_time > 2.0 {
+                Some(lead_time - 2.0)
+            } else {
+                None
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        assert_eq!(percentiles(Vec::new()), None);
+        let values: Vec<f64> = (1..=20).rev().map(f64::from).collect();
+        assert_eq!(
+            percentiles(values),
+            Some(Percentiles {
+                p50: 10.0,
+                p85: 17.0,
+                p95: 19.0
+            })
+        );
+        assert_eq!(
+            percentiles(vec![3.0]),
+            Some(Percentiles {
+                p50: 3.0,
+                p85: 3.0,
+                p95: 3.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_flow_report() {
+        let items = vec![
+            flow_item(1, "high", &["bug"], 1.0),
+            flow_item(2, "normal", &["bug", "ui"], 5.0),
+            flow_item(3, "normal", &[], 3.0),
+        ];
+        let uut = flow_report(&items, 2);
+        let groups: Vec<(&str, usize)> = uut
+            .groups
+            .iter()
+            .map(|g| (g.group.as_str(), g.tasks))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("all", 3),
+                ("priority: high", 1),
+                ("priority: normal", 2),
+                ("label: bug", 2),
+                ("label: ui", 1)
+            ]
+        );
+        assert_eq!(uut.groups[0].lead_time.as_ref().unwrap().p50, 3.0);
+        assert_eq!(uut.groups[1].cycle_time, None);
+        assert_eq!(uut.groups[2].cycle_time.as_ref().unwrap().p95, 3.0);
+        let slowest: Vec<u32> = uut.slowest.iter().map(|i| i.id).collect();
+        assert_eq!(slowest, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_flow_items() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let db = db::get_db(dbfile.path()).unwrap();
+        for (descr, start) in &[
+            ("started", Some("2019-05-02 10:00:00")),
+            (
+                "started and completed in the same second",
+                Some("2019-05-03 10:00:00"),
+            ),
+            ("never started", None),
+        ] {
+            let id = db::add_task(&db, descr).unwrap();
+            db::add_step(&db, id, "start").unwrap();
+            db::set_creation_date(&db, id, "2019-05-01 10:00:00").unwrap();
+            if let Some(start) = start {
+                db::set_step_completion_date(&db, id, 0, start).unwrap();
+            }
+            db::complete_task(&db, id).unwrap();
+            db::set_status(&db, id, "done").unwrap();
+            db::complete_steps(&db, id).unwrap();
+            db::set_completion_date(&db, id, "2019-05-03 10:00:00").unwrap();
+        }
+        let uut = flow_items(&db, None, &ShowParams::default(), &DateRange::default()).unwrap();
+        let times: Vec<(f64, Option<f64>)> =
+            uut.iter().map(|i| (i.lead_time, i.cycle_time)).collect();
+        assert_eq!(times, vec![(2.0, Some(1.0)), (2.0, Some(0.0)), (2.0, None)]);
+    }
+
+    #[test]
+    fn test_days_between() {
+        assert_eq!(
+            days_between("2019-05-01 00:00:00", "2019-05-02 12:00:00"),
+            Some(1.5)
+        );
+        assert_eq!(days_between("2019-05-01", "2019-05-02 12:00:00"), None);
+    }
+
+    #[test]
+    fn test_week_start() {
+        assert_eq!(
+            week_start(NaiveDate::from_ymd(2019, 5, 19)),
+            NaiveDate::from_ymd(2019, 5, 13)
+        );
+        assert_eq!(
+            week_start(NaiveDate::from_ymd(2019, 5, 13)),
+            NaiveDate::from_ymd(2019, 5, 13)
+        );
+    }
+}
@@ -1,13 +1,88 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Error as JsonError;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The version of the JSON documents produced by the show commands
+pub const TASK_LIST_VERSION: u32 = 1;
+
+/// A task, as exchanged with other programs
+/// The fields added after the first version have a default, so older
+/// documents can still be read
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: u32,
     pub descr: String,
     pub priority: String,
     pub status: String,
     pub storypoints: u32,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub due_date: Option<String>,
+    #[serde(default)]
+    pub completion_date: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    #[serde(default)]
+    pub creation_date: Option<String>,
+    /// The number of completed steps, the start step excluded
+    #[serde(default)]
+    pub steps_done: u32,
+    /// The number of steps, the start step excluded
+    #[serde(default)]
+    pub steps_total: u32,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// An open step of a task
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub id: u32,
+    pub descr: String,
+}
+
+/// The counters of a list of tasks
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub tasks: u64,
+    pub storypoints: u32,
+    pub status: BTreeMap<String, u64>,
+    pub assignee: BTreeMap<String, u64>,
+}
+
+/// The story points of a sprint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SprintSummary {
+    pub id: u32,
+    pub name: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub closed_date: Option<String>,
+    pub capacity: u32,
+    pub committed: u32,
+    pub completed: u32,
+}
+
+/// The JSON document of a show command
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskList {
+    pub version: u32,
+    pub view: String,
+    pub tasks: Vec<Task>,
+    pub stats: Stats,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprint: Option<SprintSummary>,
+}
+
+/// The name of a command, to know how to decode it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandName {
+    pub name: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +96,23 @@ pub struct ShowOneTaskReply {
     pub result: Task,
 }
 
+/// Stop the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shutdown {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownReply {
+    pub result: String,
+}
+
+/// The reply to a command that failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorReply {
+    pub error: String,
+}
+
 pub trait JsonCommand
 where
     Self: Serialize,
@@ -31,6 +123,7 @@ where
 }
 
 impl JsonCommand for ShowOneTask {}
+impl JsonCommand for Shutdown {}
 
 pub trait JsonReply<'a>
 where
@@ -42,6 +135,8 @@ where
 }
 
 impl JsonReply<'_> for ShowOneTaskReply {}
+impl JsonReply<'_> for ShutdownReply {}
+impl JsonReply<'_> for ErrorReply {}
 
 #[cfg(test)]
 mod op_test {
@@ -58,4 +153,17 @@ mod op_test {
         assert_eq!(output.name, uut.name);
         assert_eq!(output.id, uut.id);
     }
+
+    #[test]
+    fn test_task_compatibility() {
+        let uut: Task = serde_json::from_str(
+            r#"{"id":1,"descr":"a task","priority":"high","status":"todo","storypoints":3}"#,
+        )
+        .unwrap();
+        assert_eq!(uut.id, 1);
+        assert!(uut.labels.is_empty());
+        assert_eq!(uut.reference, None);
+        let output: Task = serde_json::from_slice(&serde_json::to_vec(&uut).unwrap()).unwrap();
+        assert_eq!(output, uut);
+    }
 }
@@ -0,0 +1,188 @@
+//! Output formats of the views
+//!
+//! The views collect their data first and then render it in one of the
+//! supported formats: an ASCII table, JSON or a flat text table (csv, tsv and
+//! markdown) that is easy to process in scripts
+use prettytable::cell::Cell;
+use prettytable::row::Row;
+use prettytable::{Attr, Table};
+use strum_macros::{Display, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+pub enum Format {
+    #[strum(serialize = "table")]
+    Table,
+    #[strum(serialize = "json")]
+    Json,
+    #[strum(serialize = "csv")]
+    Csv,
+    #[strum(serialize = "markdown", serialize = "md")]
+    Markdown,
+    #[strum(serialize = "tsv")]
+    Tsv,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Table
+    }
+}
+
+impl Format {
+    /// The separator of multiple values in a single cell
+    /// The ASCII table has multi-line cells, the other formats one line per row
+    pub fn value_separator(self) -> &'static str {
+        match self {
+            Format::Table => "\n",
+            _ => "; ",
+        }
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn tsv_escape(value: &str) -> String {
+    value.replace(&['\t', '\n', '\r'][..], " ")
+}
+
+fn markdown_escape(value: &str) -> String {
+    value
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Render a header and rows as a flat text table
+/// Only csv, tsv and markdown are flat formats, the others render as csv
+pub fn to_flat(format: Format, headers: &[String], rows: &[Vec<String>]) -> String {
+    let line = |cells: &[String]| -> String {
+        match format {
+            Format::Tsv => cells
+                .iter()
+                .map(|c| tsv_escape(c))
+                .collect::<Vec<_>>()
+                .join("\t"),
+            Format::Markdown => format!(
+                "| {} |",
+                cells
+                    .iter()
+                    .map(|c| markdown_escape(c))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ),
+            _ => cells
+                .iter()
+                .map(|c| csv_escape(c))
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    };
+    let mut rv = line(headers);
+    rv.push('\n');
+    if format == Format::Markdown {
+        rv.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+    }
+    for row in rows {
+        rv.push_str(&line(row));
+        rv.push('\n');
+    }
+    rv
+}
+
+/// Build an ASCII table, with bold headers and the first column in bold
+pub fn to_table(headers: &[String], rows: &[Vec<String>]) -> Table {
+    let mut table = Table::new();
+    table.set_titles(Row::new(
+        headers
+            .iter()
+            .map(|h| Cell::new(h).with_style(Attr::Bold))
+            .collect(),
+    ));
+    for row in rows {
+        table.add_row(Row::new(
+            row.iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    if i == 0 {
+                        Cell::new(c).with_style(Attr::Bold)
+                    } else {
+                        Cell::new(c)
+                    }
+                })
+                .collect(),
+        ));
+    }
+    table
+}
+
+/// Print a generic report, a header and its rows, in the desired format
+/// In JSON, every row is an object with the headers as keys
+pub fn print_report(format: Format, headers: &[String], rows: &[Vec<String>]) {
+    match format {
+        Format::Table => {
+            to_table(headers, rows).printstd();
+        }
+        Format::Json => {
+            let objects: Vec<serde_json::Map<String, serde_json::Value>> = rows
+                .iter()
+                .map(|r| {
+                    headers
+                        .iter()
+                        .cloned()
+                        .zip(r.iter().map(|c| serde_json::Value::String(c.clone())))
+                        .collect()
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&objects).unwrap_or_default()
+            );
+        }
+        _ => print!("{}", to_flat(format, headers, rows)),
+    }
+}
+
+#[cfg(test)]
+mod output_tests {
+    use super::*;
+
+    fn sample() -> (Vec<String>, Vec<Vec<String>>) {
+        (
+            vec!["Id".to_string(), "Description".to_string()],
+            vec![
+                vec!["1".to_string(), "fix, \"the\" bug".to_string()],
+                vec!["2".to_string(), "a|b\tc".to_string()],
+            ],
+        )
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+        assert_eq!("md".parse::<Format>().unwrap(), Format::Markdown);
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_to_flat() {
+        let (headers, rows) = sample();
+        assert_eq!(
+            to_flat(Format::Csv, &headers, &rows),
+            "Id,Description\n1,\"fix, \"\"the\"\" bug\"\n2,a|b\tc\n"
+        );
+        assert_eq!(
+            to_flat(Format::Tsv, &headers, &rows),
+            "Id\tDescription\n1\tfix, \"the\" bug\n2\ta|b c\n"
+        );
+        assert_eq!(
+            to_flat(Format::Markdown, &headers, &rows),
+            "| Id | Description |\n|---|---|\n| 1 | fix, \"the\" bug |\n| 2 | a\\|b\tc |\n"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Text reports, ready to be pasted in a chat or in a document
+use crate::config::ChangelogConfig;
+use crate::db;
+use crate::op;
+use crate::sort::SortSpec;
+use crate::task::{self, DateRange};
+use chrono::prelude::*;
+use chrono::Duration;
+use rusqlite::{Connection, Error};
+
+/// The previous working day, skipping the weekends
+pub fn previous_working_day(today: NaiveDate) -> NaiveDate {
+    match today.weekday() {
+        Weekday::Mon => today - Duration::days(3),
+        Weekday::Sun => today - Duration::days(2),
+        _ => today.pred(),
+    }
+}
+
+/// The tasks selected for a standup meeting
+#[derive(Debug, Default)]
+pub struct Standup {
+    /// The first day of the completed tasks
+    pub since: Option<NaiveDate>,
+    pub done: Vec<op::Task>,
+    /// The tasks in progress, with their open steps
+    pub in_progress: Vec<op::Task>,
+    pub blocked: Vec<op::Task>,
+}
+
+/// Collect the tasks completed since the previous working day, the tasks in
+/// progress and the blocked ones, with the label and assignee filters
+pub fn standup(
+    db: &Connection,
+    today: NaiveDate,
+    labels: &[String],
+    assignee: Option<&str>,
+) -> Result<Standup, Error> {
+    let since = previous_working_day(today);
+    let range = DateRange::from_local_days(Some(since), None);
+    let mut rv = Standup {
+        since: Some(since),
+        ..Default::default()
+    };
+    for t in db::get_done_tasks_where(db, None, &range, &SortSpec::done_default())? {
+        let task_labels = db::get_labels(db, t.id)?;
+        if task::check_label(labels, &task_labels) && task::check_assignee(assignee, &t.assignee) {
+            rv.done.push(task::done_to_op_task(db, &t, task_labels));
+        }
+    }
+    for t in db::get_open_tasks(db)? {
+        let task_labels = db::get_labels(db, t.id)?;
+        if !task::check_label(labels, &task_labels) || !task::check_assignee(assignee, &t.assignee)
+        {
+            continue;
+        }
+        match t.status.as_str() {
+            "in_progress" => {
+                let mut op_task = task::to_op_task(db, &t, task_labels, true);
+                // the start step is done, once the task is in progress
+                op_task.steps.retain(|s| s.id > 0);
+                rv.in_progress.push(op_task);
+            }
+            "block" => rv
+                .blocked
+                .push(task::to_op_task(db, &t, task_labels, false)),
+            _ => {}
+        }
+    }
+    Ok(rv)
+}
+
+fn standup_section(title: &str, tasks: &[op::Task], markdown: bool) -> String {
+    let mut rv = if markdown {
+        format!("**{}**\n", title)
+    } else {
+        format!("{}:\n", title)
+    };
+    if tasks.is_empty() {
+        rv.push_str(if markdown {
+            "_nothing_\n"
+        } else {
+            "  nothing\n"
+        });
+    }
+    for t in tasks {
+        let progress = if t.steps_total > 0 {
+            format!(" ({}/{} steps)", t.steps_done, t.steps_total)
+        } else {
+            String::new()
+        };
+        if markdown {
+            rv.push_str(&format!("- #{} {}{}\n", t.id, t.descr.trim_end(), progress));
+        } else {
+            rv.push_str(&format!("  #{} {}{}\n", t.id, t.descr.trim_end(), progress));
+        }
+        for s in &t.steps {
+            if markdown {
+                rv.push_str(&format!("  - [ ] {}\n", s.descr.trim_end()));
+            } else {
+                rv.push_str(&format!("      {}. {}\n", s.id, s.descr.trim_end()));
+            }
+        }
+    }
+    rv
+}
+
+/// Render the standup report as plain text or as markdown
+pub fn standup_to_string(standup: &Standup, markdown: bool) -> String {
+    let done_title = match standup.since {
+        Some(since) => format!("Done since {}", since.format("%a %Y-%m-%d")),
+        None => "Done".to_string(),
+    };
+    [
+        standup_section(&done_title, &standup.done, markdown),
+        standup_section("In progress", &standup.in_progress, markdown),
+        standup_section("Blocked", &standup.blocked, markdown),
+    ]
+    .join("\n")
+}
+
+/// The order of the sections in Keep a Changelog; other sections follow
+const SECTION_ORDER: [&str; 7] = [
+    "Added",
+    "Changed",
+    "Deprecated",
+    "Removed",
+    "Fix",
+    "Fixed",
+    "Security",
+];
+
+fn section_rank(section: &str) -> usize {
+    SECTION_ORDER
+        .iter()
+        .position(|s| *s == section)
+        .unwrap_or(SECTION_ORDER.len())
+}
+
+/// The section of a task: the first section, in changelog order, with a
+/// label of the task, or the default section
+fn task_section<'a>(config: &'a ChangelogConfig, labels: &[String]) -> Option<&'a str> {
+    config
+        .sections
+        .iter()
+        .filter(|(filter, _)| labels.iter().any(|l| task::label_match(filter, l)))
+        .map(|(_, section)| section.as_str())
+        .min_by_key(|section| section_rank(section))
+        .or(config.default_section.as_deref())
+}
+
+/// Group the tasks in the changelog sections, in changelog order
+pub fn changelog_sections(
+    config: &ChangelogConfig,
+    tasks: Vec<op::Task>,
+) -> Vec<(String, Vec<op::Task>)> {
+    let mut sections: Vec<(String, Vec<op::Task>)> = Vec::new();
+    for t in tasks {
+        if let Some(section) = task_section(config, &t.labels) {
+            match sections.iter_mut().find(|(s, _)| s == section) {
+                Some((_, tasks)) => tasks.push(t),
+                None => sections.push((section.to_string(), vec![t])),
+            }
+        }
+    }
+    sections.sort_by(|(a, _), (b, _)| (section_rank(a), a).cmp(&(section_rank(b), b)));
+    sections
+}
+
+/// Collect the done tasks of the range, grouped in the changelog sections
+pub fn changelog(
+    db: &Connection,
+    config: &ChangelogConfig,
+    range: &DateRange,
+) -> Result<Vec<(String, Vec<op::Task>)>, Error> {
+    let mut tasks = Vec::new();
+    for t in db::get_done_tasks_where(db, None, range, &SortSpec::done_default())? {
+        let labels = db::get_labels(db, t.id)?;
+        tasks.push(task::done_to_op_task(db, &t, labels));
+    }
+    Ok(changelog_sections(config, tasks))
+}
+
+/// A reference as markdown: URLs become links
+fn reference_to_markdown(reference: &str) -> String {
+    if reference.starts_with("http://") || reference.starts_with("https://") {
+        let text = reference
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference);
+        format!("[{}]({})", text, reference)
+    } else {
+        reference.to_string()
+    }
+}
+
+/// Render the changelog sections as a Keep a Changelog markdown block
+/// Without a version, the header is Unreleased
+pub fn changelog_to_string(
+    sections: &[(String, Vec<op::Task>)],
+    version: Option<&str>,
+    date: NaiveDate,
+) -> String {
+    let mut rv = match version {
+        Some(version) => format!("## [{}] {}\n", version, date),
+        None => "## [Unreleased]\n".to_string(),
+    };
+    for (section, tasks) in sections {
+        rv.push_str(&format!("### {}\n", section));
+        for t in tasks {
+            rv.push_str(&format!("- {}", t.descr.trim_end()));
+            let references: Vec<String> = t
+                .reference
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .map(str::trim)
+                .filter(|r| !r.is_empty())
+                .map(reference_to_markdown)
+                .collect();
+            if !references.is_empty() {
+                rv.push_str(&format!(" ({})", references.join(", ")));
+            }
+            rv.push('\n');
+        }
+    }
+    rv
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+
+    fn done(descr: &str, labels: &[&str], reference: Option<&str>) -> op::Task {
+        op::Task {
+            descr: descr.to_string(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            reference: reference.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changelog() {
+        let mut config = ChangelogConfig::default();
+        config
+            .sections
+            .insert("area:*".to_string(), "Infra".to_string());
+        let tasks = vec![
+            done(
+                "fix the crash",
+                &["bug"],
+                Some("https://example.com/issues/12"),
+            ),
+            done("no label", &[], None),
+            done("new command", &["feature", "bug"], Some("see the docs")),
+            done("new ci", &["area:ci"], None),
+            done("faster show", &["enhancement"], None),
+        ];
+        let sections = changelog_sections(&config, tasks.clone());
+        let names: Vec<&str> = sections.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(names, vec!["Added", "Changed", "Fix", "Infra"]);
+        assert_eq!(
+            changelog_to_string(&sections, Some("0.4.0"), NaiveDate::from_ymd(2019, 6, 1)),
+            "## [0.4.0] 2019-06-01\n\
+             ### Added\n- new command (see the docs)\n\
+             ### Changed\n- faster show\n\
+             ### Fix\n- fix the crash ([12](https://example.com/issues/12))\n\
+             ### Infra\n- new ci\n"
+        );
+        config.default_section = Some("Changed".to_string());
+        let sections = changelog_sections(&config, tasks);
+        assert_eq!(sections[1].1.len(), 2);
+        assert!(
+            changelog_to_string(&sections, None, NaiveDate::from_ymd(2019, 6, 1))
+                .starts_with("## [Unreleased]\n### Added\n")
+        );
+    }
+
+    #[test]
+    fn test_previous_working_day() {
+        let d = |day| NaiveDate::from_ymd(2019, 5, day);
+        // from Monday 13 to Sunday 19
+        assert_eq!(previous_working_day(d(13)), d(10));
+        assert_eq!(previous_working_day(d(14)), d(13));
+        assert_eq!(previous_working_day(d(18)), d(17));
+        assert_eq!(previous_working_day(d(19)), d(17));
+    }
+
+    #[test]
+    fn test_standup_to_string() {
+        let uut = Standup {
+            since: Some(NaiveDate::from_ymd(2019, 5, 10)),
+            done: vec![op::Task {
+                id: 3,
+                descr: "fix the parser ".to_string(),
+                ..Default::default()
+            }],
+            in_progress: vec![op::Task {
+                id: 7,
+                descr: "write docs".to_string(),
+                steps: vec![op::Step {
+                    id: 2,
+                    descr: "review".to_string(),
+                }],
+                steps_done: 1,
+                steps_total: 2,
+                ..Default::default()
+            }],
+            blocked: Vec::new(),
+        };
+        assert_eq!(
+            standup_to_string(&uut, false),
+            "Done since Fri 2019-05-10:\n  #3 fix the parser\n\n\
+             In progress:\n  #7 write docs (1/2 steps)\n      2. review\n\n\
+             Blocked:\n  nothing\n"
+        );
+        assert_eq!(
+            standup_to_string(&uut, true),
+            "**Done since Fri 2019-05-10**\n- #3 fix the parser\n\n\
+             **In progress**\n- #7 write docs (1/2 steps)\n  - [ ] review\n\n\
+             **Blocked**\n_nothing_\n"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Sort specifications for the task views
+//!
+//! A sort specification is a comma separated list of keys, each one optionally
+//! followed by `:asc` or `:desc`, e.g. `priority,age:desc,id`
+use failure::Fail;
+use std::str::FromStr;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum SortError {
+    #[fail(display = "Unknown sort key {}", _0)]
+    UnknownKey(String),
+    #[fail(display = "Unknown sort direction {}, use asc or desc", _0)]
+    UnknownDirection(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Priority,
+    Id,
+    Age,
+    Status,
+    StoryPoints,
+    Due,
+    Label,
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec {
+    pub keys: Vec<SortKey>,
+}
+
+impl SortSpec {
+    pub fn new(field: SortField) -> Self {
+        SortSpec {
+            keys: vec![SortKey {
+                field,
+                descending: false,
+            }],
+        }
+    }
+
+    /// The default order of the open tasks: by priority
+    pub fn open_default() -> Self {
+        SortSpec::new(SortField::Priority)
+    }
+
+    /// The default order of the done tasks: by completion date
+    pub fn done_default() -> Self {
+        SortSpec::new(SortField::Completed)
+    }
+
+    /// The ORDER BY clause, referring to the tables todos (t) and todo_due (d)
+    /// The task id is always the last key, to have a stable order
+    pub fn to_sql(&self) -> String {
+        let mut clauses: Vec<String> = self
+            .keys
+            .iter()
+            .map(|k| {
+                let dir = if k.descending { "DESC" } else { "ASC" };
+                match k.field {
+                    SortField::Priority => format!("t.priority_id {}", dir),
+                    SortField::Id => format!("t.id {}", dir),
+                    // the youngest task has the latest creation date
+                    SortField::Age => {
+                        format!(
+                            "t.creation_date {}",
+                            if k.descending { "ASC" } else { "DESC" }
+                        )
+                    }
+                    SortField::Status => format!("t.status_id {}", dir),
+                    SortField::StoryPoints => format!("COALESCE(t.story_points, 0) {}", dir),
+                    SortField::Due => format!("d.due_date IS NULL, d.due_date {}", dir),
+                    SortField::Label => format!(
+                        "(SELECT MIN(l.label) FROM todo_label l WHERE l.todo_id = t.id) IS NULL, \
+                         (SELECT MIN(l.label) FROM todo_label l WHERE l.todo_id = t.id) {}",
+                        dir
+                    ),
+                    SortField::Completed => format!("t.completion_date {}", dir),
+                }
+            })
+            .collect();
+        clauses.push("t.id ASC".to_string());
+        clauses.join(", ")
+    }
+}
+
+impl FromStr for SortField {
+    type Err = SortError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "priority" | "prio" => Ok(SortField::Priority),
+            "id" => Ok(SortField::Id),
+            "age" => Ok(SortField::Age),
+            "status" => Ok(SortField::Status),
+            "sp" | "storypoints" => Ok(SortField::StoryPoints),
+            "due" => Ok(SortField::Due),
+            "label" => Ok(SortField::Label),
+            "completed" => Ok(SortField::Completed),
+            _ => Err(SortError::UnknownKey(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for SortSpec {
+    type Err = SortError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::new();
+        for k in s.split(',').map(str::trim) {
+            let mut parts = k.splitn(2, ':');
+            let field = parts.next().unwrap_or_default().parse()?;
+            let descending = match parts.next() {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(d) => return Err(SortError::UnknownDirection(d.to_string())),
+            };
+            keys.push(SortKey { field, descending });
+        }
+        Ok(SortSpec { keys })
+    }
+}
+
+#[cfg(test)]
+mod sort_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let uut: SortSpec = "priority, age:desc,due:asc".parse().unwrap();
+        assert_eq!(
+            uut.keys,
+            vec![
+                SortKey {
+                    field: SortField::Priority,
+                    descending: false
+                },
+                SortKey {
+                    field: SortField::Age,
+                    descending: true
+                },
+                SortKey {
+                    field: SortField::Due,
+                    descending: false
+                }
+            ]
+        );
+        assert_eq!(
+            "size".parse::<SortSpec>(),
+            Err(SortError::UnknownKey("size".to_string()))
+        );
+        assert_eq!(
+            "id:up".parse::<SortSpec>(),
+            Err(SortError::UnknownDirection("up".to_string()))
+        );
+        assert!("".parse::<SortSpec>().is_err());
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let uut: SortSpec = "status:desc,age".parse().unwrap();
+        assert_eq!(
+            uut.to_sql(),
+            "t.status_id DESC, t.creation_date DESC, t.id ASC"
+        );
+    }
+}
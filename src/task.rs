@@ -1,10 +1,17 @@
 use super::db;
+use super::op;
+use super::output::{self, Format};
 use chrono::prelude::*;
 use chrono::Duration;
+use failure::Fail;
 use prettytable::cell::Cell;
+use prettytable::row::Row;
 use prettytable::{cell, row, Attr, Table};
 use rusqlite::Connection;
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
 use strum_macros::{Display, EnumString};
 
 #[derive(Debug, Clone)]
@@ -14,17 +21,25 @@ pub struct Task {
     pub priority: String,
     pub status: String,
     pub storypoints: u32,
+    pub assignee: Option<String>,
+    pub due_date: Option<String>,
+    pub creation_date: String,
 }
 
 #[derive(Debug)]
 pub struct TaskDone {
     pub id: u32,
     pub descr: String,
+    pub priority: String,
     pub completion_date: String,
     pub storypoints: u32,
+    pub assignee: Option<String>,
+    pub creation_date: String,
 }
 
-#[derive(Debug, EnumString, Display)]
+/// A calendar time window, in the local timezone
+/// Weeks start on Monday; week, month, quarter and year are the current ones
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
 pub enum TimeWindow {
     #[strum(serialize = "today")]
     Today,
@@ -32,8 +47,105 @@ pub enum TimeWindow {
     Yesterday,
     #[strum(serialize = "week")]
     Week,
+    #[strum(serialize = "last-week")]
+    LastWeek,
     #[strum(serialize = "month")]
     Month,
+    #[strum(serialize = "last-month")]
+    LastMonth,
+    #[strum(serialize = "quarter")]
+    Quarter,
+    #[strum(serialize = "last-quarter")]
+    LastQuarter,
+    #[strum(serialize = "year")]
+    Year,
+    #[strum(serialize = "last-year")]
+    LastYear,
+}
+
+/// The first day of the month, `months` months after the month of `day`
+fn month_start(day: NaiveDate, months: i32) -> NaiveDate {
+    let month0 = day.year() * 12 + day.month0() as i32 + months;
+    NaiveDate::from_ymd(month0.div_euclid(12), month0.rem_euclid(12) as u32 + 1, 1)
+}
+
+impl TimeWindow {
+    /// The first and the last day of the window
+    pub fn days(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let week_start = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+        let quarter_start = month_start(today, -((today.month0() % 3) as i32));
+        let year_start = NaiveDate::from_ymd(today.year(), 1, 1);
+        let (first, next) = match self {
+            TimeWindow::Today => (today, today.succ()),
+            TimeWindow::Yesterday => (today.pred(), today),
+            TimeWindow::Week => (week_start, week_start + Duration::weeks(1)),
+            TimeWindow::LastWeek => (week_start - Duration::weeks(1), week_start),
+            TimeWindow::Month => (month_start(today, 0), month_start(today, 1)),
+            TimeWindow::LastMonth => (month_start(today, -1), month_start(today, 0)),
+            TimeWindow::Quarter => (quarter_start, month_start(quarter_start, 3)),
+            TimeWindow::LastQuarter => (month_start(quarter_start, -3), quarter_start),
+            TimeWindow::Year => (year_start, NaiveDate::from_ymd(today.year() + 1, 1, 1)),
+            TimeWindow::LastYear => (NaiveDate::from_ymd(today.year() - 1, 1, 1), year_start),
+        };
+        (first, next.pred())
+    }
+
+    /// The time range of the window, relative to the local current day
+    pub fn range(self) -> DateRange {
+        let (first, last) = self.days(Local::today().naive_local());
+        DateRange::from_local_days(Some(first), Some(last))
+    }
+}
+
+/// The beginning of a local day, in UTC as stored in the database
+pub(crate) fn local_midnight(day: NaiveDate) -> NaiveDateTime {
+    let midnight = day.and_hms(0, 0, 0);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or(midnight, |t| t.naive_utc())
+}
+
+/// A range of time, in UTC as the dates stored in the database
+/// The start is included and the end excluded; a missing bound means unbounded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DateRange {
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+}
+
+impl DateRange {
+    /// The range covering the local days from `first` to `last`, both included
+    pub fn from_local_days(first: Option<NaiveDate>, last: Option<NaiveDate>) -> Self {
+        DateRange {
+            start: first.map(local_midnight),
+            end: last.map(|d| local_midnight(d.succ())),
+        }
+    }
+
+    /// The SQL condition on a date column, the bounds are added to `values`
+    pub fn to_sql(&self, column: &str, values: &mut Vec<rusqlite::types::Value>) -> String {
+        let mut conditions = vec!["1".to_string()];
+        for (bound, op) in &[(self.start, ">="), (self.end, "<")] {
+            if let Some(bound) = bound {
+                values.push(rusqlite::types::Value::Text(
+                    bound.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ));
+                conditions.push(format!("{} {} ?{}", column, op, values.len()));
+            }
+        }
+        conditions.join(" AND ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sprint {
+    pub id: u32,
+    pub name: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub capacity: u32,
+    pub closed_date: Option<String>,
 }
 
 #[derive(Debug)]
@@ -44,26 +156,47 @@ pub struct Step {
     pub completion_date: String,
 }
 
-fn check_label(labels: &[String], task_labels: &[String]) -> bool {
-    if labels.is_empty() {
-        return true;
-    }
-    for l in labels {
-        if !task_labels.contains(l) {
-            return false;
-        }
+/// Return the namespace of a label, the part before the last `:`
+/// e.g. `area:backend` is in the namespace `area`
+pub fn label_namespace(label: &str) -> Option<&str> {
+    label.rfind(':').map(|i| &label[..i])
+}
+
+/// Check if a label matches a filter
+/// A filter ending with `:*` selects the whole namespace, sub-namespaces included
+pub fn label_match(filter: &str, label: &str) -> bool {
+    if filter.ends_with(":*") {
+        label.starts_with(&filter[..filter.len() - 1])
+    } else {
+        filter == label
     }
-    true
+}
+
+pub(crate) fn check_label(labels: &[String], task_labels: &[String]) -> bool {
+    labels
+        .iter()
+        .all(|l| task_labels.iter().any(|tl| label_match(l, tl)))
 }
 
 fn label_to_str(labels: &[String]) -> String {
-    let mut rv = String::new();
-    if !labels.is_empty() {
-        for l in labels {
-            rv.push_str(l);
-            rv.push('\n');
+    let mut plain: Vec<&str> = Vec::new();
+    let mut namespaces: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for l in labels {
+        match label_namespace(l) {
+            Some(ns) => namespaces.entry(ns).or_default().push(&l[ns.len() + 1..]),
+            None => plain.push(l),
         }
     }
+    plain.sort();
+    let mut rv = String::new();
+    for l in plain {
+        rv.push_str(l);
+        rv.push('\n');
+    }
+    for (ns, mut values) in namespaces {
+        values.sort();
+        rv.push_str(&format!("{}: {}\n", ns, values.join(", ")));
+    }
     rv
 }
 
@@ -74,77 +207,426 @@ pub struct ShowParams<'a> {
     pub status: &'a str,
     pub storypoints: bool,
     pub steps: bool,
+    pub assignee: Option<&'a str>,
+    pub format: Format,
+    /// The columns to show, the default ones of the view if not specified
+    pub columns: Option<&'a [Column]>,
+}
+
+pub(crate) fn check_assignee(assignee: Option<&str>, task_assignee: &Option<String>) -> bool {
+    match assignee {
+        Some(a) => task_assignee.as_deref() == Some(a),
+        None => true,
+    }
+}
+
+fn assignee_to_str(assignee: &Option<String>) -> &str {
+    assignee.as_deref().unwrap_or("-")
+}
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum ColumnError {
+    #[fail(display = "Unknown column {}", _0)]
+    UnknownColumn(String),
+}
+
+/// The columns of the task views
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Id,
+    Priority,
+    Status,
+    Labels,
+    Descr,
+    StoryPoints,
+    Reference,
+    Completed,
+    Created,
+    Due,
+    Assignee,
+    /// The age of the task in days, until its completion for the done tasks
+    Age,
+    /// The number of completed steps over the number of steps
+    Steps,
+}
+
+impl FromStr for Column {
+    type Err = ColumnError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Column::Id),
+            "priority" | "prio" => Ok(Column::Priority),
+            "status" => Ok(Column::Status),
+            "labels" | "label" => Ok(Column::Labels),
+            "descr" => Ok(Column::Descr),
+            "sp" | "storypoints" => Ok(Column::StoryPoints),
+            "ref" | "reference" => Ok(Column::Reference),
+            "completed" => Ok(Column::Completed),
+            "created" => Ok(Column::Created),
+            "due" => Ok(Column::Due),
+            "assignee" => Ok(Column::Assignee),
+            "age" => Ok(Column::Age),
+            "steps" => Ok(Column::Steps),
+            _ => Err(ColumnError::UnknownColumn(s.to_string())),
+        }
+    }
+}
+
+/// A comma separated list of columns, e.g. `id,descr,labels,age`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Columns(pub Vec<Column>);
+
+impl FromStr for Columns {
+    type Err = ColumnError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|c| c.trim().parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Columns)
+    }
+}
+
+impl TryFrom<String> for Columns {
+    type Error = ColumnError;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
 }
 
-fn show_stats(stats: &HashMap<&str, u64>) {
+fn parse_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok()
+}
+
+/// The age in days of a task, until its completion if done
+fn age_in_days(t: &op::Task, now: NaiveDateTime) -> Option<i64> {
+    let created = parse_date(t.creation_date.as_ref()?)?;
+    let end = match &t.completion_date {
+        Some(completed) => parse_date(completed)?,
+        None => now,
+    };
+    Some((end - created).num_days())
+}
+
+impl Column {
+    fn title(self) -> &'static str {
+        match self {
+            Column::Id => "Id",
+            Column::Priority => "Priority",
+            Column::Status => "Status",
+            Column::Labels => "Labels",
+            Column::Descr => "Description",
+            Column::StoryPoints => "Story points",
+            Column::Reference => "Reference",
+            Column::Completed => "Completed at",
+            Column::Created => "Created at",
+            Column::Due => "Due",
+            Column::Assignee => "Assignee",
+            Column::Age => "Age",
+            Column::Steps => "Steps",
+        }
+    }
+
+    /// The content of the cell, multiple values are joined by `sep`
+    fn value(self, t: &op::Task, sep: &str) -> String {
+        match self {
+            Column::Id => t.id.to_string(),
+            Column::Priority => t.priority.clone(),
+            Column::Status => t.status.clone(),
+            Column::Labels => label_to_str(&t.labels)
+                .trim_end()
+                .split('\n')
+                .collect::<Vec<_>>()
+                .join(sep),
+            Column::Descr => t.descr.clone(),
+            Column::StoryPoints => t.storypoints.to_string(),
+            Column::Reference => t
+                .reference
+                .as_deref()
+                .unwrap_or_default()
+                .lines()
+                .collect::<Vec<_>>()
+                .join(sep),
+            Column::Completed => t.completion_date.clone().unwrap_or_default(),
+            Column::Created => t.creation_date.clone().unwrap_or_default(),
+            Column::Due => t.due_date.clone().unwrap_or_default(),
+            Column::Assignee => t.assignee.clone().unwrap_or_default(),
+            Column::Age => age_in_days(t, Utc::now().naive_utc())
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            Column::Steps => {
+                if t.steps_total == 0 {
+                    String::new()
+                } else {
+                    format!("{}/{}", t.steps_done, t.steps_total)
+                }
+            }
+        }
+    }
+}
+
+/// The columns of a view: the requested ones or the default ones of the view,
+/// plus the optional ones requested by the parameters
+fn view_columns(default: &[Column], param: &ShowParams) -> Vec<Column> {
+    let mut columns = param.columns.unwrap_or(default).to_vec();
+    if param.storypoints && !columns.contains(&Column::StoryPoints) {
+        columns.push(Column::StoryPoints);
+    }
+    if param.reference && !columns.contains(&Column::Reference) {
+        columns.push(Column::Reference);
+    }
+    columns
+}
+
+/// What to print below the task table
+#[derive(Debug, PartialEq)]
+enum Summary {
+    Nothing,
+    Count,
+    Stats,
+}
+
+/// The data of a task view, collected before the rendering
+#[derive(Debug)]
+struct TaskView {
+    columns: Vec<Column>,
+    list: op::TaskList,
+    summary: Summary,
+}
+
+impl TaskView {
+    fn new(view: &str, columns: Vec<Column>, summary: Summary) -> Self {
+        TaskView {
+            columns,
+            list: op::TaskList {
+                version: op::TASK_LIST_VERSION,
+                view: view.to_string(),
+                ..Default::default()
+            },
+            summary,
+        }
+    }
+
+    fn push(&mut self, t: op::Task) {
+        let stats = &mut self.list.stats;
+        stats.tasks += 1;
+        stats.storypoints += t.storypoints;
+        *stats.status.entry(t.status.clone()).or_insert(0) += 1;
+        *stats
+            .assignee
+            .entry(assignee_to_str(&t.assignee).to_string())
+            .or_insert(0) += 1;
+        self.list.tasks.push(t);
+    }
+}
+
+fn get_op_steps(db: &Connection, task_id: u32) -> Vec<op::Step> {
+    db::get_steps(db, task_id)
+        .expect("Error occured when getting steps")
+        .into_iter()
+        .map(|s| op::Step {
+            id: s.step_id,
+            descr: s.descr,
+        })
+        .collect()
+}
+
+fn get_op_reference(db: &Connection, task_id: u32) -> Option<String> {
+    Some(db::get_refs(db, task_id).unwrap_or_default()).filter(|r| !r.is_empty())
+}
+
+/// Collect the data of an open task
+pub(crate) fn to_op_task(db: &Connection, t: &Task, labels: Vec<String>, steps: bool) -> op::Task {
+    let (steps_done, steps_total) = db::get_steps_progress(db, t.id).unwrap_or_default();
+    op::Task {
+        id: t.id,
+        descr: t.descr.clone(),
+        priority: t.priority.clone(),
+        status: t.status.clone(),
+        storypoints: t.storypoints,
+        labels,
+        reference: get_op_reference(db, t.id),
+        assignee: t.assignee.clone(),
+        due_date: t.due_date.clone(),
+        completion_date: None,
+        steps: if steps {
+            get_op_steps(db, t.id)
+        } else {
+            Vec::new()
+        },
+        creation_date: Some(t.creation_date.clone()),
+        steps_done,
+        steps_total,
+        note: db::get_note(db, t.id).unwrap_or_default(),
+    }
+}
+
+/// Collect the data of a task, open or done, with its open steps
+pub fn get_op_task(db: &Connection, task_id: u32) -> Result<op::Task, rusqlite::Error> {
+    let t = db::get_task(db, task_id)?;
+    let labels = db::get_labels(db, task_id)?;
+    let mut rv = to_op_task(db, &t, labels, true);
+    rv.completion_date = db::get_task_completion_date(db, task_id)?;
+    Ok(rv)
+}
+
+/// Collect the data of a done task
+pub(crate) fn done_to_op_task(db: &Connection, t: &TaskDone, labels: Vec<String>) -> op::Task {
+    let (steps_done, steps_total) = db::get_steps_progress(db, t.id).unwrap_or_default();
+    op::Task {
+        id: t.id,
+        descr: t.descr.clone(),
+        priority: t.priority.clone(),
+        status: "done".to_string(),
+        storypoints: t.storypoints,
+        labels,
+        reference: get_op_reference(db, t.id),
+        assignee: t.assignee.clone(),
+        due_date: None,
+        completion_date: Some(t.completion_date.clone()),
+        steps: Vec::new(),
+        creation_date: Some(t.creation_date.clone()),
+        steps_done,
+        steps_total,
+        note: db::get_note(db, t.id).unwrap_or_default(),
+    }
+}
+
+fn show_stats(stats: &op::Stats) {
     let mut stattable = Table::new();
-    for st in stats.keys() {
-        let num = stats.get(st).unwrap_or(&0).to_string();
-        let row = row![ b -> "status", &st, b -> "tasks", num];
+    for (st, num) in &stats.status {
+        let row = row![ b -> "status", st, b -> "tasks", num.to_string()];
+        stattable.add_row(row);
+    }
+    for (person, num) in &stats.assignee {
+        let row = row![ b -> "assignee", person, b -> "tasks", num.to_string()];
         stattable.add_row(row);
     }
     stattable.printstd();
 }
 
-fn set_title(table: &mut Table, param: &ShowParams) {
-    let mut title = row![b => "Id", "Priority", "Status", "Labels", "Description"];
-    if param.storypoints {
-        title.add_cell(Cell::new("Story points").with_style(Attr::Bold));
-    }
-    if param.reference {
-        title.add_cell(Cell::new("Reference").with_style(Attr::Bold));;
-    }
-    table.set_titles(title);
+/// The step rows, below the row of their task
+/// The step number goes in the Status column and its description in the
+/// Description one; without a Status column, the number precedes the description
+fn step_row(columns: &[Column], step: &op::Step) -> Row {
+    let with_status = columns.contains(&Column::Status);
+    Row::new(
+        columns
+            .iter()
+            .map(|c| match c {
+                Column::Priority => Cell::new("Step").with_style(Attr::Bold),
+                Column::Status => Cell::new(&step.id.to_string()),
+                Column::Descr if with_status => Cell::new(&step.descr),
+                Column::Descr => Cell::new(&format!("Step {}: {}", step.id, step.descr)),
+                _ => Cell::new(""),
+            })
+            .collect(),
+    )
 }
 
-fn show_steps(db: &Connection, table: &mut Table, task_id: u32, param: &ShowParams) {
-    let steps = db::get_steps(db, task_id).expect("Error occured when getting steps");
-    for step in steps {
-        let mut row = row!["", b -> "Step", &step.step_id.to_string(), "", &step.descr];
-        if param.storypoints {
-            row.add_cell(Cell::new(""));
+fn render(view: &TaskView, param: &ShowParams) {
+    let sep = param.format.value_separator();
+    match param.format {
+        Format::Table => {
+            let mut table = Table::new();
+            table.set_titles(Row::new(
+                view.columns
+                    .iter()
+                    .map(|c| Cell::new(c.title()).with_style(Attr::Bold))
+                    .collect(),
+            ));
+            for t in &view.list.tasks {
+                let mut row = Row::new(
+                    view.columns
+                        .iter()
+                        .map(|c| Cell::new(&c.value(t, sep)))
+                        .collect(),
+                );
+                if view.columns.first() == Some(&Column::Id) {
+                    row.set_cell(Cell::new(&t.id.to_string()).with_style(Attr::Bold), 0)
+                        .unwrap_or_default();
+                }
+                table.add_row(row);
+                for step in &t.steps {
+                    table.add_row(step_row(&view.columns, step));
+                }
+            }
+            table.printstd();
+            match view.summary {
+                Summary::Count => println!("tasks: {}", view.list.stats.tasks),
+                Summary::Stats => show_stats(&view.list.stats),
+                Summary::Nothing => {}
+            }
         }
-        if param.reference {
-            row.add_cell(Cell::new(""));
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&view.list).expect("Failed to serialize the tasks")
+        ),
+        _ => {
+            let mut headers: Vec<String> =
+                view.columns.iter().map(|c| c.title().to_string()).collect();
+            if param.steps {
+                headers.push("Open steps".to_string());
+            }
+            let rows: Vec<Vec<String>> = view
+                .list
+                .tasks
+                .iter()
+                .map(|t| {
+                    let mut row: Vec<String> =
+                        view.columns.iter().map(|c| c.value(t, sep)).collect();
+                    if param.steps {
+                        row.push(
+                            t.steps
+                                .iter()
+                                .map(|s| format!("{}: {}", s.id, s.descr))
+                                .collect::<Vec<_>>()
+                                .join(sep),
+                        );
+                    }
+                    row
+                })
+                .collect();
+            print!("{}", output::to_flat(param.format, &headers, &rows));
         }
-        table.add_row(row);
     }
 }
 
+const TASK_COLUMNS: [Column; 5] = [
+    Column::Id,
+    Column::Priority,
+    Column::Status,
+    Column::Labels,
+    Column::Descr,
+];
+
 pub fn show2(db: &Connection, tasks: &[Task], param: ShowParams) {
-    let mut stats = HashMap::new();
-    let mut table = Table::new();
-    set_title(&mut table, &param);
+    let summary = if !param.status.is_empty() {
+        Summary::Count
+    } else if tasks.len() != 1 {
+        Summary::Stats
+    } else {
+        Summary::Nothing
+    };
+    let view_name = if !param.status.is_empty() {
+        param.status
+    } else {
+        "all"
+    };
+    let mut view = TaskView::new(view_name, view_columns(&TASK_COLUMNS, &param), summary);
     for t in tasks {
         let task_labels: Vec<String> = db::get_labels(&db, t.id).unwrap_or_default();
         if check_label(param.label, &task_labels)
+            && check_assignee(param.assignee, &t.assignee)
             && (param.status == "" || t.status == param.status)
         {
-            let mut row = row![ b -> &t.id.to_string(), &t.priority, &t.status, &label_to_str(&task_labels), &t.descr];
-            if param.storypoints {
-                row.add_cell(Cell::new(&t.storypoints.to_string()));
-            }
-            if param.reference {
-                let reference_str = db::get_refs(&db, t.id).unwrap_or_default();
-                row.add_cell(Cell::new(&reference_str));
-            }
-            table.add_row(row);
-            let counter = stats.entry(t.status.as_str()).or_insert(0u64);
-            *counter += 1;
-            if param.steps {
-                show_steps(&db, &mut table, t.id, &param);
-            }
+            view.push(to_op_task(db, t, task_labels, param.steps));
         }
     }
-    table.printstd();
-    if param.status != "" {
-        println!("tasks: {}", stats.get(param.status).unwrap_or(&0));
-    } else if tasks.len() != 1 {
-        show_stats(&stats);
-    }
+    render(&view, &param);
 }
 
-pub fn show1task(db: &Connection, task_id: u32) {
+pub fn show1task(db: &Connection, task_id: u32, format: Format) {
     let tasks = db::get_open_tasks(db).unwrap_or_default();
     let task: Vec<_> = tasks.iter().filter(|x| x.id == task_id).cloned().collect();
     if !task.is_empty() {
@@ -157,106 +639,273 @@ pub fn show1task(db: &Connection, task_id: u32) {
                 reference: true,
                 storypoints: true,
                 steps: true,
+                assignee: None,
+                format,
+                columns: None,
             },
         );
+        if format == Format::Table {
+            if let Ok(Some(note)) = db::get_note(db, task_id) {
+                println!("Note:\n{}", note);
+            }
+        }
+    }
+}
+
+/// Show the tasks of a sprint, with the committed and completed story points
+/// `tasks` pairs every task with its completion within the sprint
+pub fn show_sprint(db: &Connection, sprint: &Sprint, tasks: &[(Task, bool)], param: ShowParams) {
+    let mut summary = op::SprintSummary {
+        id: sprint.id,
+        name: sprint.name.clone(),
+        start_date: sprint.start_date.clone(),
+        end_date: sprint.end_date.clone(),
+        closed_date: sprint.closed_date.clone(),
+        capacity: sprint.capacity,
+        ..Default::default()
+    };
+    let mut columns = TASK_COLUMNS.to_vec();
+    columns.push(Column::StoryPoints);
+    let mut view = TaskView::new("sprint", view_columns(&columns, &param), Summary::Nothing);
+    for (t, done) in tasks {
+        let task_labels: Vec<String> = db::get_labels(db, t.id).unwrap_or_default();
+        if check_label(param.label, &task_labels) && check_assignee(param.assignee, &t.assignee) {
+            summary.committed += t.storypoints;
+            if *done {
+                summary.completed += t.storypoints;
+            }
+            view.push(to_op_task(db, t, task_labels, param.steps));
+        }
+    }
+    if param.format != Format::Table {
+        view.list.sprint = Some(summary);
+        render(&view, &param);
+        return;
+    }
+    println!(
+        "Sprint {}: {} [{} - {}]{}",
+        sprint.id,
+        sprint.name,
+        sprint.start_date,
+        sprint.end_date,
+        if sprint.closed_date.is_some() {
+            " closed"
+        } else {
+            ""
+        }
+    );
+    render(&view, &param);
+    let mut stattable = Table::new();
+    stattable.add_row(row![ b -> "capacity", summary.capacity.to_string()]);
+    stattable.add_row(row![ b -> "committed", summary.committed.to_string()]);
+    stattable.add_row(row![ b -> "completed", summary.completed.to_string()]);
+    stattable.add_row(row![ b -> "remaining", (summary.committed - summary.completed).to_string()]);
+    stattable.printstd();
+    if summary.committed > summary.capacity {
+        println!(
+            "Warning: committed story points exceed the capacity by {}",
+            summary.committed - summary.capacity
+        );
     }
 }
 
-pub fn show_short(
-    db: &Connection,
-    tasks: &[Task],
-    label: &[String],
-    reference: bool,
-    storypoints: bool,
-) {
-    let mut stats = HashMap::new();
-    let mut table = Table::new();
-    let param = ShowParams {
-        label,
-        status: "",
-        reference,
-        storypoints,
-        steps: true,
+pub fn show_short(db: &Connection, tasks: &[Task], param: ShowParams) {
+    let summary = if tasks.len() != 1 {
+        Summary::Stats
+    } else {
+        Summary::Nothing
     };
-    set_title(&mut table, &param);
+    let mut view = TaskView::new("short", view_columns(&TASK_COLUMNS, &param), summary);
     for t in tasks {
         let task_labels: Vec<String> = db::get_labels(db, t.id).unwrap_or_default();
-        if check_label(label, &task_labels)
+        if check_label(param.label, &task_labels)
+            && check_assignee(param.assignee, &t.assignee)
             && (t.status == "block"
                 || t.status == "in_progress"
                 || t.priority == "high"
                 || t.priority == "urgent")
         {
-            let label_str = label_to_str(&task_labels);
-            let mut row =
-                row![ b -> &t.id.to_string(), &t.priority, &t.status, &label_str, &t.descr];
-            if storypoints {
-                row.add_cell(Cell::new(&t.storypoints.to_string()));
-            }
-            if reference {
-                let reference_str = db::get_refs(db, t.id).unwrap_or_default();
-                row.add_cell(Cell::new(&reference_str));
-            }
-            table.add_row(row);
-            show_steps(&db, &mut table, t.id, &param);
-            let counter = stats.entry(t.status.as_str()).or_insert(0u64);
-            *counter += 1;
+            view.push(to_op_task(db, t, task_labels, param.steps));
         }
     }
-    table.printstd();
-    if tasks.len() != 1 {
-        show_stats(&stats);
+    render(&view, &param);
+}
+
+pub fn show_done(db: &Connection, tasks: &[TaskDone], param: ShowParams) {
+    let columns = [Column::Id, Column::Labels, Column::Descr, Column::Completed];
+    let mut view = TaskView::new("done", view_columns(&columns, &param), Summary::Count);
+    for t in tasks {
+        let task_labels: Vec<String> = db::get_labels(db, t.id).unwrap_or_default();
+        if check_label(param.label, &task_labels) && check_assignee(param.assignee, &t.assignee) {
+            view.push(done_to_op_task(db, t, task_labels));
+        }
     }
+    render(&view, &param);
 }
 
-pub fn show_done(
-    db: &Connection,
-    tasks: &[TaskDone],
-    label: &[String],
-    timewindow: &TimeWindow,
-    reference: bool,
-    storypoints: bool,
-) {
-    let mut stats = HashMap::new();
-    let mut table = Table::new();
-    let mut title = row![b => "Id", "Labels", "Description", "Completed at"];
-    if storypoints {
-        title.add_cell(Cell::new("Story points").with_style(Attr::Bold));
+#[cfg(test)]
+mod task_tests {
+    use super::*;
+
+    #[test]
+    fn test_time_window_days() {
+        // a Wednesday
+        let today = NaiveDate::from_ymd(2019, 5, 15);
+        let d = |y, m, d| NaiveDate::from_ymd(y, m, d);
+        let expected = vec![
+            (TimeWindow::Today, (d(2019, 5, 15), d(2019, 5, 15))),
+            (TimeWindow::Yesterday, (d(2019, 5, 14), d(2019, 5, 14))),
+            (TimeWindow::Week, (d(2019, 5, 13), d(2019, 5, 19))),
+            (TimeWindow::LastWeek, (d(2019, 5, 6), d(2019, 5, 12))),
+            (TimeWindow::Month, (d(2019, 5, 1), d(2019, 5, 31))),
+            (TimeWindow::LastMonth, (d(2019, 4, 1), d(2019, 4, 30))),
+            (TimeWindow::Quarter, (d(2019, 4, 1), d(2019, 6, 30))),
+            (TimeWindow::LastQuarter, (d(2019, 1, 1), d(2019, 3, 31))),
+            (TimeWindow::Year, (d(2019, 1, 1), d(2019, 12, 31))),
+            (TimeWindow::LastYear, (d(2018, 1, 1), d(2018, 12, 31))),
+        ];
+        for (window, days) in expected {
+            assert_eq!(window.days(today), days, "{}", window);
+        }
+        let new_year = d(2019, 1, 1);
+        assert_eq!(
+            TimeWindow::Yesterday.days(new_year),
+            (d(2018, 12, 31), d(2018, 12, 31))
+        );
+        assert_eq!(
+            TimeWindow::LastQuarter.days(new_year),
+            (d(2018, 10, 1), d(2018, 12, 31))
+        );
+        assert_eq!(
+            "last-month".parse::<TimeWindow>().unwrap(),
+            TimeWindow::LastMonth
+        );
     }
-    if reference {
-        title.add_cell(Cell::new("Reference").with_style(Attr::Bold));;
+
+    #[test]
+    fn test_date_range_to_sql() {
+        let mut values = Vec::new();
+        assert_eq!(
+            DateRange::default().to_sql("t.completion_date", &mut values),
+            "1"
+        );
+        let uut = DateRange {
+            start: Some(NaiveDate::from_ymd(2019, 5, 1).and_hms(0, 0, 0)),
+            end: Some(NaiveDate::from_ymd(2019, 6, 1).and_hms(0, 0, 0)),
+        };
+        values.push(rusqlite::types::Value::Integer(1));
+        assert_eq!(
+            uut.to_sql("t.completion_date", &mut values),
+            "1 AND t.completion_date >= ?2 AND t.completion_date < ?3"
+        );
+        assert_eq!(
+            values[2],
+            rusqlite::types::Value::Text("2019-06-01 00:00:00".to_string())
+        );
     }
-    table.set_titles(title);
-    for t in tasks {
-        let now: DateTime<Utc> = Utc::now();
-        let completed: NaiveDateTime =
-            NaiveDateTime::parse_from_str(t.completion_date.as_str(), "%Y-%m-%d %H:%M:%S").unwrap();
-        let duration = now.naive_utc() - completed;
-        let max_duration = match timewindow {
-            TimeWindow::Today => Duration::days(1),
-            TimeWindow::Yesterday => Duration::days(2),
-            TimeWindow::Week => Duration::weeks(1),
-            TimeWindow::Month => Duration::weeks(4),
+
+    #[test]
+    fn test_columns_parse() {
+        let uut: Columns = "id, descr,labels,age,due,sp,ref,steps".parse().unwrap();
+        assert_eq!(
+            uut.0,
+            vec![
+                Column::Id,
+                Column::Descr,
+                Column::Labels,
+                Column::Age,
+                Column::Due,
+                Column::StoryPoints,
+                Column::Reference,
+                Column::Steps
+            ]
+        );
+        assert_eq!(
+            "id,size".parse::<Columns>(),
+            Err(ColumnError::UnknownColumn("size".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_computed_columns() {
+        let mut t = op::Task {
+            creation_date: Some("2019-06-01 10:00:00".to_string()),
+            steps_done: 1,
+            steps_total: 3,
+            ..Default::default()
         };
-        if duration < max_duration {
-            let task_labels: Vec<String> = db::get_labels(db, t.id).unwrap_or_default();
-            if check_label(label, &task_labels) {
-                let label_str = label_to_str(&task_labels);
-                let mut row =
-                    row![ b -> &t.id.to_string(), &label_str, &t.descr, &t.completion_date];
-                if storypoints {
-                    row.add_cell(Cell::new(&t.storypoints.to_string()));
-                }
-                if reference {
-                    let reference_str = db::get_refs(db, t.id).unwrap_or_default();
-                    row.add_cell(Cell::new(&reference_str));
-                }
-                table.add_row(row);
-                let counter = stats.entry("done").or_insert(0);
-                *counter += 1;
-            }
-        }
+        let now = parse_date("2019-06-11 09:00:00").unwrap();
+        assert_eq!(age_in_days(&t, now), Some(9));
+        t.completion_date = Some("2019-06-04 12:00:00".to_string());
+        assert_eq!(age_in_days(&t, now), Some(3));
+        assert_eq!(Column::Steps.value(&t, "\n"), "1/3");
+        t.steps_total = 0;
+        assert_eq!(Column::Steps.value(&t, "\n"), "");
+    }
+
+    #[test]
+    fn test_view_columns() {
+        let columns = [Column::Id, Column::Descr];
+        let param = ShowParams {
+            storypoints: true,
+            reference: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            view_columns(&TASK_COLUMNS, &ShowParams::default()),
+            TASK_COLUMNS.to_vec()
+        );
+        assert_eq!(
+            view_columns(&[Column::Id, Column::Reference], &param),
+            vec![Column::Id, Column::Reference, Column::StoryPoints]
+        );
+        let param = ShowParams {
+            columns: Some(&columns),
+            ..Default::default()
+        };
+        assert_eq!(view_columns(&TASK_COLUMNS, &param), columns.to_vec());
+    }
+
+    #[test]
+    fn test_label_namespace() {
+        assert_eq!(label_namespace("backend"), None);
+        assert_eq!(label_namespace("area:backend"), Some("area"));
+        assert_eq!(label_namespace("area:backend:db"), Some("area:backend"));
+    }
+
+    #[test]
+    fn test_label_match() {
+        assert!(label_match("area:backend", "area:backend"));
+        assert!(!label_match("area:backend", "area:frontend"));
+        assert!(label_match("area:*", "area:backend"));
+        assert!(label_match("area:*", "area:backend:db"));
+        assert!(!label_match("area:*", "area"));
+        assert!(!label_match("area:*", "areas:backend"));
+    }
+
+    #[test]
+    fn test_check_label() {
+        let task_labels = vec!["area:backend".to_string(), "size:S".to_string()];
+        assert!(check_label(&[], &task_labels));
+        assert!(check_label(&["area:*".to_string()], &task_labels));
+        assert!(check_label(
+            &["area:*".to_string(), "size:S".to_string()],
+            &task_labels
+        ));
+        assert!(!check_label(&["team:*".to_string()], &task_labels));
+    }
+
+    #[test]
+    fn test_label_to_str_grouped() {
+        let labels = vec![
+            "size:S".to_string(),
+            "urgent".to_string(),
+            "area:frontend".to_string(),
+            "area:backend".to_string(),
+        ];
+        assert_eq!(
+            label_to_str(&labels),
+            "urgent\narea: backend, frontend\nsize: S\n"
+        );
     }
-    table.printstd();
-    println!("tasks: {}", stats.get("done").unwrap_or(&0));
 }
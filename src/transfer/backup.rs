@@ -0,0 +1,381 @@
+//! Export and import of the whole database as a versioned JSON document
+//!
+//! The document doesn't depend on the database schema, so it can be used to
+//! move the tasks to a database created by a newer version
+use super::{create_all, NewStep, NewTask, TransferError};
+use crate::db;
+use crate::sort::SortSpec;
+use chrono::prelude::*;
+use rusqlite::{Connection, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The version of the backup document
+pub const BACKUP_VERSION: u32 = 1;
+
+/// A step, the start step has id 0
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupStep {
+    pub id: u32,
+    pub descr: String,
+    #[serde(default)]
+    pub completion_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupTask {
+    pub id: u32,
+    pub descr: String,
+    pub priority: String,
+    pub status: String,
+    pub storypoints: u32,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub due_date: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub creation_date: String,
+    #[serde(default)]
+    pub completion_date: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<BackupStep>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupSprint {
+    pub id: u32,
+    pub name: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub capacity: u32,
+    #[serde(default)]
+    pub closed_date: Option<String>,
+    /// The ids of the tasks of the sprint
+    #[serde(default)]
+    pub tasks: Vec<u32>,
+}
+
+/// The backup document, all the dates are UTC
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub exported: String,
+    #[serde(default)]
+    pub tasks: Vec<BackupTask>,
+    #[serde(default)]
+    pub exclusive_namespaces: Vec<String>,
+    #[serde(default)]
+    pub sprints: Vec<BackupSprint>,
+}
+
+/// How the backup is imported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    /// The tasks are added to the existing ones, with new ids
+    Merge,
+    /// The existing tasks are deleted, the ids of the backup are kept
+    Replace,
+}
+
+/// Collect the whole database in a backup document
+pub fn export(db: &Connection) -> Result<Backup, Error> {
+    let mut rv = Backup {
+        version: BACKUP_VERSION,
+        exported: Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        exclusive_namespaces: db::get_exclusive_namespaces(db)?,
+        ..Default::default()
+    };
+    for t in db::get_all_tasks(db)? {
+        let reference = db::get_refs(db, t.id)?;
+        rv.tasks.push(BackupTask {
+            id: t.id,
+            descr: t.descr,
+            priority: t.priority,
+            status: t.status,
+            storypoints: t.storypoints,
+            labels: db::get_labels(db, t.id)?,
+            reference: if reference.is_empty() {
+                None
+            } else {
+                Some(reference)
+            },
+            assignee: t.assignee,
+            due_date: t.due_date,
+            note: db::get_note(db, t.id)?,
+            creation_date: t.creation_date,
+            completion_date: db::get_task_completion_date(db, t.id)?,
+            steps: db::get_all_steps(db, t.id)?
+                .into_iter()
+                .map(|s| BackupStep {
+                    id: s.step_id,
+                    descr: s.descr,
+                    completion_date: if s.completion_date.is_empty() {
+                        None
+                    } else {
+                        Some(s.completion_date)
+                    },
+                })
+                .collect(),
+        });
+    }
+    for s in db::get_sprints(db)? {
+        let tasks = db::get_sprint_tasks(db, s.id, &SortSpec::open_default())?;
+        rv.sprints.push(BackupSprint {
+            id: s.id,
+            name: s.name,
+            start_date: s.start_date,
+            end_date: s.end_date,
+            capacity: s.capacity,
+            closed_date: s.closed_date,
+            tasks: tasks.into_iter().map(|(t, _)| t.id).collect(),
+        });
+    }
+    Ok(rv)
+}
+
+pub fn parse(input: &str) -> Result<Backup, TransferError> {
+    let backup: Backup = serde_json::from_str(input)?;
+    if backup.version > BACKUP_VERSION {
+        return Err(TransferError::UnsupportedVersion(backup.version));
+    }
+    Ok(backup)
+}
+
+/// Convert the tasks of the backup, keeping the ids in replace mode
+pub fn to_tasks(backup: &Backup, mode: ImportMode) -> Vec<NewTask> {
+    backup
+        .tasks
+        .iter()
+        .map(|t| NewTask {
+            id: if mode == ImportMode::Replace {
+                Some(t.id)
+            } else {
+                None
+            },
+            descr: t.descr.clone(),
+            status: t.status.clone(),
+            priority: Some(t.priority.clone()),
+            storypoints: Some(t.storypoints),
+            labels: t.labels.clone(),
+            reference: t.reference.clone(),
+            note: t.note.clone(),
+            assignee: t.assignee.clone(),
+            due_date: t
+                .due_date
+                .as_ref()
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+            steps: t
+                .steps
+                .iter()
+                .filter(|s| s.id > 0)
+                .map(|s| NewStep {
+                    descr: s.descr.clone(),
+                    done: s.completion_date.is_some(),
+                    completion_date: s.completion_date.clone(),
+                })
+                .collect(),
+            creation_date: Some(t.creation_date.clone()),
+            start_date: t
+                .steps
+                .iter()
+                .find(|s| s.id == 0)
+                .and_then(|s| s.completion_date.clone()),
+            completion_date: t.completion_date.clone(),
+        })
+        .collect()
+}
+
+/// Create the tasks and the sprints of the backup, in the current transaction
+/// In replace mode, the existing tasks and sprints are deleted first
+/// The exclusive namespaces are set after the tasks are created, so in merge
+/// mode they are checked against the existing tasks as well
+pub fn import(
+    db: &Connection,
+    backup: &Backup,
+    mode: ImportMode,
+) -> Result<Vec<u32>, TransferError> {
+    if mode == ImportMode::Replace {
+        db::clear_tasks(db)?;
+    }
+    let ids = create_all(db, &to_tasks(backup, mode))?;
+    for ns in &backup.exclusive_namespaces {
+        db::set_namespace_exclusive(db, ns, true).map_err(TransferError::Db)?;
+    }
+    let id_map: HashMap<u32, u32> = backup
+        .tasks
+        .iter()
+        .map(|t| t.id)
+        .zip(ids.iter().cloned())
+        .collect();
+    for s in &backup.sprints {
+        let date = |d: &str| {
+            NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                .map_err(|_| TransferError::InvalidDate(d.to_string()))
+        };
+        let mut sprint_id = db::add_sprint(
+            db,
+            &s.name,
+            &date(&s.start_date)?,
+            &date(&s.end_date)?,
+            s.capacity,
+        )?;
+        if mode == ImportMode::Replace {
+            db::set_sprint_id(db, sprint_id, s.id)?;
+            sprint_id = s.id;
+        }
+        if let Some(closed_date) = &s.closed_date {
+            db::set_sprint_closed_date(db, sprint_id, closed_date)?;
+        }
+        for t in &s.tasks {
+            if let Some(id) = id_map.get(t) {
+                db::add_sprint_task(db, sprint_id, *id)?;
+            }
+        }
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod backup_tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    fn sample_db(temp: &TempDir) -> Connection {
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let db = db::get_db(dbfile.path()).unwrap();
+        let first = db::add_task(&db, "first").unwrap();
+        db::add_step(&db, first, "start").unwrap();
+        let id = db::add_task(&db, "second").unwrap();
+        db::add_step(&db, id, "start").unwrap();
+        db::add_step(&db, id, "write").unwrap();
+        db::add_step(&db, id, "review").unwrap();
+        db::complete_step(&db, id, 0).unwrap();
+        db::set_step_completion_date(&db, id, 1, "2019-05-02 10:00:00").unwrap();
+        db::set_status(&db, id, "in_progress").unwrap();
+        db::add_labels(&db, id, &["area:docs".to_string()]).unwrap();
+        db::set_reference(&db, id, "https://example.com/1").unwrap();
+        db::set_note(&db, id, Some("a note")).unwrap();
+        db::set_namespace_exclusive(&db, "area", true).unwrap();
+        db::delete_task(&db, first).unwrap();
+        let sprint = db::add_sprint(
+            &db,
+            "sprint",
+            &NaiveDate::from_ymd(2019, 5, 1),
+            &NaiveDate::from_ymd(2019, 5, 14),
+            10,
+        )
+        .unwrap();
+        db::add_sprint_task(&db, sprint, id).unwrap();
+        db
+    }
+
+    #[test]
+    fn test_export() {
+        let temp = TempDir::new().unwrap();
+        let db = sample_db(&temp);
+        let uut = export(&db).unwrap();
+        assert_eq!(uut.version, BACKUP_VERSION);
+        assert_eq!(uut.exclusive_namespaces, vec!["area"]);
+        assert_eq!(uut.tasks.len(), 1);
+        assert_eq!(uut.tasks[0].id, 2);
+        assert_eq!(uut.tasks[0].labels, vec!["area:docs"]);
+        assert_eq!(uut.tasks[0].note, Some("a note".to_string()));
+        assert_eq!(uut.tasks[0].steps.len(), 3);
+        assert_eq!(
+            uut.tasks[0].steps[1].completion_date,
+            Some("2019-05-02 10:00:00".to_string())
+        );
+        assert_eq!(uut.sprints[0].tasks, vec![2]);
+        let json = serde_json::to_string(&uut).unwrap();
+        assert_eq!(parse(&json).unwrap(), uut);
+        let json = json.replace("\"version\":1", "\"version\":2");
+        assert!(parse(&json).is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let temp = TempDir::new().unwrap();
+        let mut db = sample_db(&temp);
+        let backup = export(&db).unwrap();
+        // replace: the ids are kept, the content is the same
+        let tx = db.transaction().unwrap();
+        assert_eq!(import(&tx, &backup, ImportMode::Replace).unwrap(), vec![2]);
+        tx.commit().unwrap();
+        let mut replaced = export(&db).unwrap();
+        replaced.exported = backup.exported.clone();
+        assert_eq!(replaced, backup);
+        // merge: new ids, the sprint follows its tasks
+        let tx = db.transaction().unwrap();
+        assert_eq!(import(&tx, &backup, ImportMode::Merge).unwrap(), vec![3]);
+        tx.commit().unwrap();
+        let merged = export(&db).unwrap();
+        assert_eq!(merged.tasks.len(), 2);
+        assert_eq!(merged.tasks[1].steps, backup.tasks[0].steps);
+        assert_eq!(merged.sprints.len(), 2);
+        assert_eq!(merged.sprints[1].tasks, vec![3]);
+    }
+
+    #[test]
+    fn test_import_merge_conflict() {
+        let temp = TempDir::new().unwrap();
+        let mut db = sample_db(&temp);
+        let mut backup = export(&db).unwrap();
+        backup.exclusive_namespaces = vec!["size".to_string()];
+        let id = db::add_task(&db, "existing").unwrap();
+        db::add_labels(&db, id, &["size:S".to_string(), "size:M".to_string()]).unwrap();
+        // the existing task breaks the rule of the backup
+        let tx = db.transaction().unwrap();
+        match import(&tx, &backup, ImportMode::Merge) {
+            Err(TransferError::Db(db::DbError::NamespaceConflict(ns, ids))) => {
+                assert_eq!(ns, "size");
+                assert_eq!(ids, id.to_string());
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        drop(tx);
+        assert!(db::get_exclusive_namespaces(&db)
+            .unwrap()
+            .iter()
+            .all(|ns| ns != "size"));
+        assert_eq!(export(&db).unwrap().tasks.len(), 2);
+    }
+
+    #[test]
+    fn test_export_0_3_1() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        // a task of a database created by 0.3.1, only the baseline schema
+        {
+            let db = db::get_db(dbfile.path()).unwrap();
+            let id = db::add_task(&db, "old").unwrap();
+            db::add_step(&db, id, "start").unwrap();
+            for table in &[
+                "label_namespace",
+                "todo_assignee",
+                "sprints",
+                "sprint_task",
+                "todo_due",
+                "todo_note",
+            ] {
+                db.execute(&format!("DROP TABLE {};", table), rusqlite::params![])
+                    .unwrap();
+            }
+        }
+        let db = db::get_db(dbfile.path()).unwrap();
+        let uut = export(&db).unwrap();
+        assert_eq!(uut.tasks.len(), 1);
+        assert_eq!(uut.tasks[0].descr, "old");
+        assert_eq!(uut.tasks[0].assignee, None);
+        assert_eq!(uut.tasks[0].note, None);
+        assert!(uut.exclusive_namespaces.is_empty());
+        assert!(uut.sprints.is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+//! Import of the rows of a CSV file, with a mapping of the columns to the
+//! task fields and translation tables for the priority and status values
+use super::{check_priority, check_status, NewTask, TransferError};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// The task fields a column can be mapped to
+pub const FIELDS: [&str; 9] = [
+    "descr", "priority", "status", "labels", "sp", "ref", "note", "assignee", "due",
+];
+
+/// Split a CSV document in records (RFC 4180): quoted fields can contain the
+/// delimiter, newlines and doubled quotes
+pub fn parse_records(input: &str, delimiter: char) -> Result<Vec<Vec<String>>, TransferError> {
+    let mut rv = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            rv.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if quoted {
+        return Err(TransferError::InvalidCsv(
+            "unterminated quoted field".to_string(),
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        rv.push(record);
+    }
+    // the empty lines
+    rv.retain(|r| r.len() > 1 || r.iter().any(|f| !f.is_empty()));
+    Ok(rv)
+}
+
+/// How the rows are converted to tasks
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvMapping {
+    /// The column of each mapped field
+    pub columns: HashMap<String, String>,
+    /// The translation of the priority values, e.g. P1 to urgent
+    pub priorities: HashMap<String, String>,
+    /// The translation of the status values, e.g. Doing to in_progress
+    pub statuses: HashMap<String, String>,
+    /// The separator of the labels in a cell
+    pub label_delimiter: String,
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        CsvMapping {
+            columns: HashMap::new(),
+            priorities: HashMap::new(),
+            statuses: HashMap::new(),
+            label_delimiter: ",".to_string(),
+        }
+    }
+}
+
+impl CsvMapping {
+    /// Add the FIELD=COLUMN mappings of a comma separated list
+    pub fn add_columns(&mut self, mappings: &str) -> Result<(), TransferError> {
+        for m in mappings.split(',').filter(|m| !m.trim().is_empty()) {
+            let (field, column) = super::parse_mapping(m)?;
+            if !FIELDS.contains(&field.as_str()) {
+                return Err(TransferError::UnknownField(field, FIELDS.join(", ")));
+            }
+            self.columns.insert(field, column);
+        }
+        Ok(())
+    }
+
+    /// Set the separator of the labels, it cannot be empty
+    pub fn set_label_delimiter(&mut self, delimiter: &str) -> Result<(), TransferError> {
+        if delimiter.is_empty() {
+            return Err(TransferError::EmptyLabelDelimiter);
+        }
+        self.label_delimiter = delimiter.to_string();
+        Ok(())
+    }
+
+    /// Add a VALUE=PRIORITY translation
+    pub fn add_priority(&mut self, mapping: &str) -> Result<(), TransferError> {
+        let (value, priority) = super::parse_mapping(mapping)?;
+        check_priority(&priority)?;
+        self.priorities.insert(value, priority);
+        Ok(())
+    }
+
+    /// Add a VALUE=STATUS translation
+    pub fn add_status(&mut self, mapping: &str) -> Result<(), TransferError> {
+        let (value, status) = super::parse_mapping(mapping)?;
+        check_status(&status)?;
+        self.statuses.insert(value, status);
+        Ok(())
+    }
+
+    /// The column index of each field: the mapped column or, if not mapped,
+    /// the column named as the field
+    fn indexes(&self, header: &[String]) -> Result<HashMap<&'static str, usize>, TransferError> {
+        let find = |name: &str| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+        };
+        let mut rv = HashMap::new();
+        for field in FIELDS.iter() {
+            match self.columns.get(*field) {
+                Some(column) => {
+                    let index =
+                        find(column).ok_or_else(|| TransferError::UnknownColumn(column.clone()))?;
+                    rv.insert(*field, index);
+                }
+                None => {
+                    if let Some(index) = find(field) {
+                        rv.insert(*field, index);
+                    }
+                }
+            }
+        }
+        if !rv.contains_key("descr") {
+            return Err(TransferError::UnknownColumn("descr".to_string()));
+        }
+        Ok(rv)
+    }
+}
+
+/// Translate a value with the table, the myrello values are accepted as they are
+fn translate(
+    table: &HashMap<String, String>,
+    value: &str,
+    check: fn(&str) -> Result<(), TransferError>,
+) -> Result<String, TransferError> {
+    if let Some(v) = table.get(value) {
+        return Ok(v.clone());
+    }
+    if let Some((_, v)) = table.iter().find(|(k, _)| k.eq_ignore_ascii_case(value)) {
+        return Ok(v.clone());
+    }
+    let lower = value.to_lowercase();
+    match check(&lower) {
+        Ok(_) => Ok(lower),
+        // the error with the value as it is in the file
+        Err(_) => check(value).map(|_| lower),
+    }
+}
+
+fn to_task(
+    record: &[String],
+    indexes: &HashMap<&'static str, usize>,
+    mapping: &CsvMapping,
+) -> Result<NewTask, TransferError> {
+    let get = |field: &str| {
+        indexes
+            .get(field)
+            .and_then(|i| record.get(*i))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+    let mut rv = NewTask {
+        descr: get("descr").ok_or(TransferError::MissingDescr)?.to_string(),
+        ..Default::default()
+    };
+    if let Some(priority) = get("priority") {
+        rv.priority = Some(translate(&mapping.priorities, priority, check_priority)?);
+    }
+    if let Some(status) = get("status") {
+        rv.status = translate(&mapping.statuses, status, check_status)?;
+    }
+    if let Some(labels) = get("labels") {
+        rv.labels = labels
+            .split(mapping.label_delimiter.as_str())
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+    if let Some(sp) = get("sp") {
+        rv.storypoints = Some(
+            sp.parse()
+                .map_err(|_| TransferError::InvalidNumber(sp.to_string()))?,
+        );
+    }
+    rv.reference = get("ref").map(str::to_string);
+    rv.note = get("note").map(str::to_string);
+    rv.assignee = get("assignee").map(str::to_string);
+    if let Some(due) = get("due") {
+        rv.due_date = Some(
+            NaiveDate::parse_from_str(due, "%Y-%m-%d")
+                .map_err(|_| TransferError::InvalidDate(due.to_string()))?,
+        );
+    }
+    Ok(rv)
+}
+
+/// The converted tasks and the bad rows, with their row number
+pub type CsvTasks = (Vec<NewTask>, Vec<(usize, TransferError)>);
+
+/// Convert the rows after the header to tasks
+/// The bad rows are returned apart, with their row number (the header is row 1)
+pub fn to_tasks(records: &[Vec<String>], mapping: &CsvMapping) -> Result<CsvTasks, TransferError> {
+    let (header, rows) = match records.split_first() {
+        Some(split) => split,
+        None => return Ok((Vec::new(), Vec::new())),
+    };
+    let indexes = mapping.indexes(header)?;
+    let mut tasks = Vec::new();
+    let mut errors = Vec::new();
+    for (i, record) in rows.iter().enumerate() {
+        match to_task(record, &indexes, mapping) {
+            Ok(task) => tasks.push(task),
+            Err(e) => errors.push((i + 2, e)),
+        }
+    }
+    Ok((tasks, errors))
+}
+
+#[cfg(test)]
+mod csv_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let uut = parse_records(
+            "a,b,c\r\n\"x, y\",\"say \"\"hi\"\"\",\"two\nlines\"\n\n1,,3",
+            ',',
+        )
+        .unwrap();
+        assert_eq!(
+            uut,
+            vec![
+                vec!["a", "b", "c"],
+                vec!["x, y", "say \"hi\"", "two\nlines"],
+                vec!["1", "", "3"],
+            ]
+        );
+        assert_eq!(parse_records("a;b\n", ';').unwrap(), vec![vec!["a", "b"]]);
+        assert!(parse_records("a,\"b\n", ',').is_err());
+    }
+
+    #[test]
+    fn test_mapping() {
+        let mut uut = CsvMapping::default();
+        uut.add_columns("descr=Title, sp = Points").unwrap();
+        assert_eq!(uut.columns.get("sp"), Some(&"Points".to_string()));
+        assert!(uut.add_columns("size=Points").is_err());
+        assert!(uut.add_priority("P1=urgent").is_ok());
+        assert!(uut.add_priority("P1=critical").is_err());
+        assert!(uut.add_status("Doing=in_progress").is_ok());
+        assert!(uut.add_status("Doing=doing").is_err());
+        assert!(uut.set_label_delimiter("").is_err());
+        assert_eq!(uut.label_delimiter, ",");
+        assert!(uut.set_label_delimiter(" | ").is_ok());
+        assert_eq!(uut.label_delimiter, " | ");
+    }
+
+    #[test]
+    fn test_to_tasks() {
+        let mut mapping = CsvMapping::default();
+        mapping
+            .add_columns("descr=Title,priority=Prio,labels=Tags,sp=Points")
+            .unwrap();
+        mapping.add_priority("P1=urgent").unwrap();
+        mapping.add_status("Doing=in_progress").unwrap();
+        mapping.set_label_delimiter(";").unwrap();
+        let records = parse_records(
+            "Title,Prio,Tags,Points,Status,Due\n\
+             write docs,p1,docs; area:web,3,doing,2019-06-01\n\
+             fix bug,High,,,Done,\n\
+             ,low,,,,\n\
+             test,P9,,,,\n\
+             deploy,,,lots,,\n\
+             release,,,,,next week\n",
+            ',',
+        )
+        .unwrap();
+        let (tasks, errors) = to_tasks(&records, &mapping).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].descr, "write docs");
+        assert_eq!(tasks[0].priority, Some("urgent".to_string()));
+        assert_eq!(tasks[0].labels, vec!["docs", "area:web"]);
+        assert_eq!(tasks[0].storypoints, Some(3));
+        assert_eq!(tasks[0].status, "in_progress");
+        assert_eq!(tasks[0].due_date, Some(NaiveDate::from_ymd(2019, 6, 1)));
+        assert_eq!(tasks[1].priority, Some("high".to_string()));
+        assert_eq!(tasks[1].status, "done");
+        let rows: Vec<usize> = errors.iter().map(|(row, _)| *row).collect();
+        assert_eq!(rows, vec![4, 5, 6, 7]);
+        // a mapped column must exist
+        mapping.add_columns("note=Comments").unwrap();
+        assert!(to_tasks(&records, &mapping).is_err());
+    }
+}
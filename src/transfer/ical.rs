@@ -0,0 +1,191 @@
+//! Export of the tasks as an iCalendar file of VTODO components (RFC 5545)
+use crate::op;
+use chrono::prelude::*;
+use rusqlite::{Connection, Error};
+
+/// The iCalendar priority: 1 is the highest, 9 the lowest
+fn priority(priority: &str) -> Option<u32> {
+    match priority {
+        "urgent" => Some(1),
+        "high" => Some(3),
+        "normal" => Some(5),
+        "low" => Some(7),
+        "miserable" => Some(9),
+        _ => None,
+    }
+}
+
+fn status(status: &str) -> &'static str {
+    match status {
+        "in_progress" => "IN-PROCESS",
+        "done" => "COMPLETED",
+        _ => "NEEDS-ACTION",
+    }
+}
+
+/// A database date (UTC) in the iCalendar UTC format
+fn to_ical_date(date: &str) -> Option<String> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|d| d.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+fn escape(text: &str) -> String {
+    text.trim_end()
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line at 75 octets, the continuation lines start with a space
+fn fold(line: &str) -> String {
+    let mut rv = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            rv.push_str("\r\n ");
+            len = 1;
+        }
+        rv.push(c);
+        len += c.len_utf8();
+    }
+    rv.push_str("\r\n");
+    rv
+}
+
+/// The content lines of a task, as a VTODO component
+pub fn to_vtodo(t: &op::Task, stamp: &str) -> Vec<String> {
+    let mut rv = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:task-{}@myrello", t.id),
+        format!("DTSTAMP:{}", stamp),
+        format!("SUMMARY:{}", escape(&t.descr)),
+        format!("STATUS:{}", status(&t.status)),
+    ];
+    if let Some(p) = priority(&t.priority) {
+        rv.push(format!("PRIORITY:{}", p));
+    }
+    if let Some(created) = t.creation_date.as_ref().and_then(|d| to_ical_date(d)) {
+        rv.push(format!("CREATED:{}", created));
+    }
+    if let Some(completed) = t.completion_date.as_ref().and_then(|d| to_ical_date(d)) {
+        rv.push(format!("COMPLETED:{}", completed));
+    }
+    if let Some(due) = t
+        .due_date
+        .as_ref()
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    {
+        rv.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+    }
+    if !t.labels.is_empty() {
+        let labels: Vec<String> = t.labels.iter().map(|l| escape(l)).collect();
+        rv.push(format!("CATEGORIES:{}", labels.join(",")));
+    }
+    if let Some(reference) = &t.reference {
+        let mut references = reference.lines().map(str::trim).filter(|r| !r.is_empty());
+        if let Some(url) = references.find(|r| r.contains("://")) {
+            rv.push(format!("URL:{}", url));
+        }
+    }
+    if let Some(note) = &t.note {
+        rv.push(format!("DESCRIPTION:{}", escape(note)));
+    }
+    if let Some(percent) = (t.steps_done * 100).checked_div(t.steps_total) {
+        rv.push(format!("PERCENT-COMPLETE:{}", percent));
+    }
+    if t.status == "block" {
+        rv.push("X-MYRELLO-STATUS:block".to_string());
+    }
+    rv.push("END:VTODO".to_string());
+    rv
+}
+
+/// The tasks as an iCalendar document
+pub fn to_ical(tasks: &[op::Task], stamp: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//myrello//myrello {}//EN",
+            env!("CARGO_PKG_VERSION")
+        ),
+    ];
+    for t in tasks {
+        lines.extend(to_vtodo(t, stamp));
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+/// The open and done tasks with the labels, as an iCalendar document
+pub fn export(db: &Connection, labels: &[String]) -> Result<String, Error> {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    Ok(to_ical(&super::op_tasks(db, labels, true)?, &stamp))
+}
+
+#[cfg(test)]
+mod ical_tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("SUMMARY:short"), "SUMMARY:short\r\n");
+        let long = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&long);
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].len() <= 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", long));
+    }
+
+    #[test]
+    fn test_to_vtodo() {
+        let t = op::Task {
+            id: 7,
+            descr: "fix; the, bug".to_string(),
+            priority: "high".to_string(),
+            status: "done".to_string(),
+            labels: vec!["bug".to_string(), "area:backend".to_string()],
+            reference: Some("ticket 12\nhttps://example.com/12".to_string()),
+            due_date: Some("2019-06-01".to_string()),
+            creation_date: Some("2019-05-01 10:00:00".to_string()),
+            completion_date: Some("2019-05-03 12:30:00".to_string()),
+            note: Some("first\nsecond".to_string()),
+            steps_done: 1,
+            steps_total: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            to_vtodo(&t, "20190601T000000Z"),
+            vec![
+                "BEGIN:VTODO",
+                "UID:task-7@myrello",
+                "DTSTAMP:20190601T000000Z",
+                "SUMMARY:fix\\; the\\, bug",
+                "STATUS:COMPLETED",
+                "PRIORITY:3",
+                "CREATED:20190501T100000Z",
+                "COMPLETED:20190503T123000Z",
+                "DUE;VALUE=DATE:20190601",
+                "CATEGORIES:bug,area:backend",
+                "URL:https://example.com/12",
+                "DESCRIPTION:first\\nsecond",
+                "PERCENT-COMPLETE:33",
+                "END:VTODO",
+            ]
+        );
+        let t = op::Task {
+            id: 8,
+            descr: "wait".to_string(),
+            status: "block".to_string(),
+            ..Default::default()
+        };
+        let uut = to_vtodo(&t, "20190601T000000Z");
+        assert!(uut.contains(&"STATUS:NEEDS-ACTION".to_string()));
+        assert!(uut.contains(&"X-MYRELLO-STATUS:block".to_string()));
+    }
+}
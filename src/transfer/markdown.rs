@@ -0,0 +1,279 @@
+//! Conversion between markdown checklists and tasks
+//!
+//! The top-level checklist items (`- [ ] do X`) are tasks and the nested items
+//! are their steps, `[x]` marks the done ones. The headings the items are
+//! under are the labels of the tasks
+use super::{NewStep, NewTask};
+use crate::db;
+use crate::op;
+use crate::task::Step;
+use rusqlite::{Connection, Error};
+
+/// A checklist item: indentation, done flag and text
+fn parse_item(line: &str) -> Option<(usize, bool, &str)> {
+    let text = line.trim_start();
+    let indent = line[..line.len() - text.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let text = if text.starts_with("- ") || text.starts_with("* ") || text.starts_with("+ ") {
+        &text[2..]
+    } else {
+        // ordered lists, as 1. or 1)
+        let digits = text.chars().take_while(char::is_ascii_digit).count();
+        let rest = &text[digits..];
+        if digits == 0 || !(rest.starts_with(". ") || rest.starts_with(") ")) {
+            return None;
+        }
+        &rest[2..]
+    };
+    let text = text.trim_start();
+    let done = if text.starts_with("[ ]") {
+        false
+    } else if text.starts_with("[x]") || text.starts_with("[X]") {
+        true
+    } else {
+        return None;
+    };
+    let descr = text[3..].trim();
+    if descr.is_empty() {
+        None
+    } else {
+        Some((indent, done, descr))
+    }
+}
+
+/// A heading: level and text
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if level == 0 || !rest.starts_with(' ') {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some((level, text))
+    }
+}
+
+/// Parse the checklists of a markdown document
+/// Everything that isn't a heading or a checklist item is ignored
+pub fn parse(input: &str) -> Vec<NewTask> {
+    let mut rv: Vec<NewTask> = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    // the indentation of the current task, None after a heading
+    let mut task_indent = None;
+    let mut in_code = false;
+    for line in input.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        if let Some((level, text)) = parse_heading(line) {
+            headings.retain(|(l, _)| *l < level);
+            headings.push((level, text.to_string()));
+            task_indent = None;
+            continue;
+        }
+        let (indent, done, descr) = match parse_item(line) {
+            Some(item) => item,
+            None => continue,
+        };
+        match (task_indent, rv.last_mut()) {
+            (Some(task_indent), Some(task)) if indent > task_indent => {
+                task.steps.push(NewStep {
+                    descr: descr.to_string(),
+                    done,
+                    ..Default::default()
+                });
+            }
+            _ => {
+                let mut labels: Vec<String> = Vec::new();
+                for (_, h) in &headings {
+                    if !labels.contains(h) {
+                        labels.push(h.clone());
+                    }
+                }
+                rv.push(NewTask {
+                    descr: descr.to_string(),
+                    status: if done { "done" } else { "todo" }.to_string(),
+                    labels,
+                    ..Default::default()
+                });
+                task_indent = Some(indent);
+            }
+        }
+    }
+    rv
+}
+
+fn checkbox(done: bool) -> &'static str {
+    if done {
+        "[x]"
+    } else {
+        "[ ]"
+    }
+}
+
+/// The tasks with their steps as markdown checklists, under the headings of
+/// their labels: a heading for the first label, a sub-heading for the second
+/// and so on
+pub fn to_markdown(tasks: &[(op::Task, Vec<Step>)]) -> String {
+    let mut sorted: Vec<&(op::Task, Vec<Step>)> = tasks.iter().collect();
+    // the tasks with the same labels are together, after the tasks with
+    // fewer labels of the same headings
+    sorted.sort_by(|(a, _), (b, _)| a.labels.cmp(&b.labels));
+    let mut rv = String::new();
+    let mut current: Vec<String> = Vec::new();
+    for (t, steps) in sorted {
+        if t.labels != current {
+            let common = current
+                .iter()
+                .zip(t.labels.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            for (level, label) in t.labels.iter().enumerate().skip(common) {
+                if !rv.is_empty() && !rv.ends_with("\n\n") {
+                    rv.push('\n');
+                }
+                rv.push_str(&format!("{} {}\n\n", "#".repeat(level + 1), label));
+            }
+            current = t.labels.clone();
+        }
+        rv.push_str(&format!(
+            "- {} {}\n",
+            checkbox(t.status == "done"),
+            t.descr.trim()
+        ));
+        for s in steps.iter().filter(|s| s.step_id > 0) {
+            rv.push_str(&format!(
+                "  - {} {}\n",
+                checkbox(!s.completion_date.is_empty()),
+                s.descr.trim()
+            ));
+        }
+    }
+    rv
+}
+
+/// The open tasks as markdown checklists, followed by the done ones if requested
+pub fn export(db: &Connection, labels: &[String], done: bool) -> Result<String, Error> {
+    let mut tasks = Vec::new();
+    for t in super::op_tasks(db, labels, done)? {
+        let steps = db::get_all_steps(db, t.id)?;
+        tasks.push((t, steps));
+    }
+    Ok(to_markdown(&tasks))
+}
+
+#[cfg(test)]
+mod markdown_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = "# Design\n\
+                     \n\
+                     Some text\n\
+                     - not a checklist\n\
+                     - [ ] first task\n\
+                     \x20 - [x] done step\n\
+                     \x20 - [ ] open step\n\
+                     \x20   - [ ] deeper step\n\
+                     - [x] done task\n\
+                     ## Backend\n\
+                     \x20 * [X] indented task\n\
+                     \x20   1. [ ] its step\n\
+                     ```\n\
+                     - [ ] in a code block\n\
+                     ```\n\
+                     # Other #\n\
+                     1) [ ] last\n";
+        let uut = parse(input);
+        assert_eq!(uut.len(), 4);
+        assert_eq!(uut[0].descr, "first task");
+        assert_eq!(uut[0].status, "todo");
+        assert_eq!(uut[0].labels, vec!["Design"]);
+        let steps: Vec<(&str, bool)> = uut[0]
+            .steps
+            .iter()
+            .map(|s| (s.descr.as_str(), s.done))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("done step", true),
+                ("open step", false),
+                ("deeper step", false)
+            ]
+        );
+        assert_eq!(uut[1].descr, "done task");
+        assert_eq!(uut[1].status, "done");
+        assert_eq!(uut[2].descr, "indented task");
+        assert_eq!(uut[2].status, "done");
+        assert_eq!(uut[2].labels, vec!["Design", "Backend"]);
+        assert_eq!(uut[2].steps.len(), 1);
+        assert_eq!(uut[3].descr, "last");
+        assert_eq!(uut[3].labels, vec!["Other"]);
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let task = |id: u32, descr: &str, status: &str, labels: &[&str]| op::Task {
+            id,
+            descr: descr.to_string(),
+            status: status.to_string(),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            ..Default::default()
+        };
+        let step = |step_id: u32, descr: &str, completion_date: &str| Step {
+            todo_id: 1,
+            step_id,
+            descr: descr.to_string(),
+            completion_date: completion_date.to_string(),
+        };
+        let tasks = vec![
+            (task(1, "api", "todo", &["Design", "Backend"]), vec![]),
+            (
+                task(2, "write", "in_progress", &["Design"]),
+                vec![
+                    step(0, "start", "2019-05-01 10:00:00"),
+                    step(1, "outline", "2019-05-02 10:00:00"),
+                    step(2, "review", ""),
+                ],
+            ),
+            (task(3, "loose", "done", &[]), vec![]),
+            (task(4, "ui", "todo", &["Design", "Frontend"]), vec![]),
+        ];
+        let md = to_markdown(&tasks);
+        assert_eq!(
+            md,
+            "- [x] loose\n\
+             \n\
+             # Design\n\
+             \n\
+             - [ ] write\n\
+             \x20 - [x] outline\n\
+             \x20 - [ ] review\n\
+             \n\
+             ## Backend\n\
+             \n\
+             - [ ] api\n\
+             \n\
+             ## Frontend\n\
+             \n\
+             - [ ] ui\n"
+        );
+        let back = parse(&md);
+        assert_eq!(back.len(), 4);
+        assert_eq!(back[1].descr, "write");
+        assert_eq!(back[1].steps.len(), 2);
+        assert!(back[1].steps[0].done);
+        assert_eq!(back[3].labels, vec!["Design", "Frontend"]);
+    }
+}
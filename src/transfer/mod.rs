@@ -0,0 +1,414 @@
+//! Import and export of tasks from and to other tools
+//!
+//! Every importer converts its input into a list of `NewTask`, that can be
+//! shown to the user before being created in the database, in a single
+//! transaction
+pub mod backup;
+pub mod csv;
+pub mod ical;
+pub mod markdown;
+pub mod taskwarrior;
+pub mod todotxt;
+pub mod trello;
+
+use crate::db::{self, DbError};
+use crate::op;
+use crate::sort::SortSpec;
+use crate::task::{self, DateRange};
+use chrono::NaiveDate;
+use failure::Fail;
+use rusqlite::Connection;
+
+#[derive(Fail, Debug)]
+pub enum TransferError {
+    #[fail(display = "Failed to read the input: {}", _0)]
+    Io(#[cause] std::io::Error),
+    #[fail(display = "Invalid JSON document: {}", _0)]
+    Json(#[cause] serde_json::Error),
+    #[fail(display = "Unknown status {}", _0)]
+    UnknownStatus(String),
+    #[fail(display = "Unknown priority {}", _0)]
+    UnknownPriority(String),
+    #[fail(display = "Unknown column {}", _0)]
+    UnknownColumn(String),
+    #[fail(display = "Unknown field {}, expected one of {}", _0, _1)]
+    UnknownField(String, String),
+    #[fail(display = "Invalid number {}", _0)]
+    InvalidNumber(String),
+    #[fail(display = "Invalid CSV document: {}", _0)]
+    InvalidCsv(String),
+    #[fail(display = "Missing description")]
+    MissingDescr,
+    #[fail(display = "The label delimiter cannot be empty")]
+    EmptyLabelDelimiter,
+    #[fail(display = "Invalid mapping {}, expected NAME=VALUE", _0)]
+    InvalidMapping(String),
+    #[fail(display = "Invalid date {}", _0)]
+    InvalidDate(String),
+    #[fail(display = "Unsupported document version {}", _0)]
+    UnsupportedVersion(u32),
+    #[fail(display = "Database error: {}", _0)]
+    Db(#[cause] DbError),
+    #[fail(display = "Failed to create task {}: {}", _0, _1)]
+    Task(String, #[cause] DbError),
+}
+
+impl From<std::io::Error> for TransferError {
+    fn from(e: std::io::Error) -> Self {
+        TransferError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for TransferError {
+    fn from(e: rusqlite::Error) -> Self {
+        TransferError::Db(e.into())
+    }
+}
+
+impl From<serde_json::Error> for TransferError {
+    fn from(e: serde_json::Error) -> Self {
+        TransferError::Json(e)
+    }
+}
+
+pub const STATUSES: [&str; 4] = ["todo", "in_progress", "done", "block"];
+pub const PRIORITIES: [&str; 5] = ["urgent", "high", "normal", "low", "miserable"];
+
+/// Check that the status is one of the myrello statuses
+pub fn check_status(status: &str) -> Result<(), TransferError> {
+    if STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(TransferError::UnknownStatus(status.to_string()))
+    }
+}
+
+/// Check that the priority is one of the myrello priorities
+pub fn check_priority(priority: &str) -> Result<(), TransferError> {
+    if PRIORITIES.contains(&priority) {
+        Ok(())
+    } else {
+        Err(TransferError::UnknownPriority(priority.to_string()))
+    }
+}
+
+/// Parse a NAME=VALUE mapping, as given on the command line
+pub fn parse_mapping(mapping: &str) -> Result<(String, String), TransferError> {
+    let mut split = mapping.splitn(2, '=');
+    match (split.next(), split.next()) {
+        (Some(name), Some(value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(TransferError::InvalidMapping(mapping.to_string())),
+    }
+}
+
+/// A step of a task to be created, the start step excluded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewStep {
+    pub descr: String,
+    pub done: bool,
+    /// The completion date of a done step, now if not known
+    pub completion_date: Option<String>,
+}
+
+/// A task to be created, with all its attributes
+/// The dates are UTC, in the database format
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTask {
+    /// The id of the task, a new one if not set
+    pub id: Option<u32>,
+    pub descr: String,
+    pub status: String,
+    pub priority: Option<String>,
+    pub storypoints: Option<u32>,
+    pub labels: Vec<String>,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+    pub assignee: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub steps: Vec<NewStep>,
+    pub creation_date: Option<String>,
+    /// The date the work started, the completion of the start step
+    pub start_date: Option<String>,
+    pub completion_date: Option<String>,
+}
+
+impl Default for NewTask {
+    fn default() -> Self {
+        NewTask {
+            id: None,
+            descr: String::new(),
+            status: "todo".to_string(),
+            priority: None,
+            storypoints: None,
+            labels: Vec::new(),
+            reference: None,
+            note: None,
+            assignee: None,
+            due_date: None,
+            steps: Vec::new(),
+            creation_date: None,
+            start_date: None,
+            completion_date: None,
+        }
+    }
+}
+
+/// The open tasks with the labels, followed by the done ones if requested
+pub fn op_tasks(
+    db: &Connection,
+    labels: &[String],
+    done: bool,
+) -> Result<Vec<op::Task>, rusqlite::Error> {
+    let mut rv = Vec::new();
+    for t in db::get_open_tasks(db)? {
+        let task_labels = db::get_labels(db, t.id)?;
+        if task::check_label(labels, &task_labels) {
+            rv.push(task::to_op_task(db, &t, task_labels, false));
+        }
+    }
+    if done {
+        let range = DateRange::default();
+        for t in db::get_done_tasks_where(db, None, &range, &SortSpec::done_default())? {
+            let task_labels = db::get_labels(db, t.id)?;
+            if task::check_label(labels, &task_labels) {
+                rv.push(task::done_to_op_task(db, &t, task_labels));
+            }
+        }
+    }
+    Ok(rv)
+}
+
+/// The tasks to be created, as a table: header and rows
+pub fn preview(tasks: &[NewTask]) -> (Vec<String>, Vec<Vec<String>>) {
+    let headers = [
+        "Description",
+        "Status",
+        "Priority",
+        "SP",
+        "Labels",
+        "Steps",
+        "Reference",
+        "Note",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect();
+    let rows = tasks
+        .iter()
+        .map(|t| {
+            vec![
+                t.descr.clone(),
+                t.status.clone(),
+                t.priority.clone().unwrap_or_default(),
+                t.storypoints.map(|sp| sp.to_string()).unwrap_or_default(),
+                t.labels.join("\n"),
+                if t.steps.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        "{}/{}",
+                        t.steps.iter().filter(|s| s.done).count(),
+                        t.steps.len()
+                    )
+                },
+                t.reference.clone().unwrap_or_default(),
+                if t.note.is_some() { "yes" } else { "" }.to_string(),
+            ]
+        })
+        .collect();
+    (headers, rows)
+}
+
+/// The description cut to the size of the db column, on a char boundary
+fn short_descr(descr: &str) -> &str {
+    let descr = descr.trim_end();
+    if descr.len() <= 128 {
+        return descr;
+    }
+    let end = (0..=128)
+        .rev()
+        .find(|i| descr.is_char_boundary(*i))
+        .unwrap_or(0);
+    &descr[..end]
+}
+
+/// Create a task, with the start step and its steps
+pub fn create_task(db: &Connection, task: &NewTask) -> Result<u32, DbError> {
+    db::check_label_rules(db, &[], &task.labels)?;
+    let mut id = db::add_task(db, short_descr(&task.descr))?;
+    if let Some(new_id) = task.id {
+        db::set_task_id(db, id, new_id)?;
+        id = new_id;
+    }
+    if let Some(creation_date) = &task.creation_date {
+        db::set_creation_date(db, id, creation_date)?;
+    }
+    if !task.labels.is_empty() {
+        db::add_labels(db, id, &task.labels)?;
+    }
+    if let Some(priority) = &task.priority {
+        db::set_priority(db, id, priority)?;
+    }
+    if let Some(storypoints) = task.storypoints {
+        db::set_storypoint(db, id, storypoints)?;
+    }
+    if let Some(reference) = &task.reference {
+        db::set_reference(db, id, reference)?;
+    }
+    if task.note.is_some() {
+        db::set_note(db, id, task.note.as_deref())?;
+    }
+    if task.assignee.is_some() {
+        db::set_assignee(db, id, task.assignee.as_deref())?;
+    }
+    if task.due_date.is_some() {
+        db::set_due_date(db, id, task.due_date.as_ref())?;
+    }
+    db::add_step(db, id, "start")?;
+    if let Some(start_date) = &task.start_date {
+        db::set_step_completion_date(db, id, 0, start_date)?;
+    }
+    for s in &task.steps {
+        let step_id = db::add_step(db, id, &s.descr)?;
+        match &s.completion_date {
+            Some(date) if s.done => db::set_step_completion_date(db, id, step_id, date)?,
+            _ if s.done => db::complete_step(db, id, step_id)?,
+            _ => {}
+        }
+    }
+    match task.status.as_str() {
+        "todo" => {}
+        "done" => {
+            db::set_status(db, id, "done")?;
+            match &task.completion_date {
+                Some(completion_date) => {
+                    db::set_completion_date(db, id, completion_date)?;
+                    // the open steps were completed with the task
+                    db::complete_steps_at(db, id, completion_date)?;
+                }
+                None => {
+                    db::complete_task(db, id)?;
+                    db::complete_steps(db, id)?;
+                }
+            }
+        }
+        status => {
+            db::set_status(db, id, status)?;
+            if status == "in_progress" && task.start_date.is_none() {
+                db::complete_step(db, id, 0)?;
+            }
+        }
+    }
+    Ok(id)
+}
+
+/// Create all the tasks, stopping at the first failure
+/// Return the ids of the new tasks
+pub fn create_all(db: &Connection, tasks: &[NewTask]) -> Result<Vec<u32>, TransferError> {
+    let mut ids = Vec::new();
+    for t in tasks {
+        let id = create_task(db, t).map_err(|e| TransferError::Task(t.descr.clone(), e))?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+/// Create all the tasks in a single transaction: if one fails, none is created
+/// Return the ids of the new tasks
+pub fn create_tasks(db: &mut Connection, tasks: &[NewTask]) -> Result<Vec<u32>, TransferError> {
+    let tx = db.transaction()?;
+    let ids = create_all(&tx, tasks)?;
+    tx.commit()?;
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod transfer_tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!(
+            parse_mapping("In review = block").unwrap(),
+            ("In review".to_string(), "block".to_string())
+        );
+        assert_eq!(
+            parse_mapping("a=b=c").unwrap(),
+            ("a".to_string(), "b=c".to_string())
+        );
+        assert!(parse_mapping("done").is_err());
+        assert!(parse_mapping("=done").is_err());
+    }
+
+    #[test]
+    fn test_short_descr() {
+        assert_eq!(short_descr("short "), "short");
+        let long = "è".repeat(100);
+        assert_eq!(short_descr(&long).len(), 128);
+    }
+
+    #[test]
+    fn test_create_tasks() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let mut db = db::get_db(dbfile.path()).unwrap();
+        let tasks = vec![
+            NewTask {
+                descr: "write the docs".to_string(),
+                status: "in_progress".to_string(),
+                labels: vec!["docs".to_string()],
+                note: Some("the user guide".to_string()),
+                steps: vec![
+                    NewStep {
+                        descr: "outline".to_string(),
+                        done: true,
+                        ..Default::default()
+                    },
+                    NewStep {
+                        descr: "review".to_string(),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            NewTask {
+                descr: "release".to_string(),
+                status: "done".to_string(),
+                creation_date: Some("2019-05-01 10:00:00".to_string()),
+                completion_date: Some("2019-05-03 10:00:00".to_string()),
+                ..Default::default()
+            },
+        ];
+        let ids = create_tasks(&mut db, &tasks).unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(db::get_steps_progress(&db, 1).unwrap(), (1, 2));
+        assert!(db::get_start_date(&db, 1).unwrap().is_some());
+        assert_eq!(db::get_note(&db, 1).unwrap(), tasks[0].note);
+        assert_eq!(
+            db::get_task_completion_date(&db, 2).unwrap(),
+            Some("2019-05-03 10:00:00".to_string())
+        );
+        // never started, the start step stays open
+        assert_eq!(db::get_start_date(&db, 2).unwrap(), None);
+        // an exclusive namespace violation aborts the whole import
+        db::set_namespace_exclusive(&db, "area", true).unwrap();
+        let tasks = vec![
+            NewTask {
+                descr: "fine".to_string(),
+                ..Default::default()
+            },
+            NewTask {
+                descr: "broken".to_string(),
+                labels: vec!["area:a".to_string(), "area:b".to_string()],
+                ..Default::default()
+            },
+        ];
+        assert!(create_tasks(&mut db, &tasks).is_err());
+        assert_eq!(db::get_open_tasks(&db).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,312 @@
+//! Import and export of the Taskwarrior JSON task format
+//!
+//! Tags are labels, the project is a label in the `project` namespace, the
+//! annotations are the note and entry, start and end are the creation, start
+//! and completion dates. What Taskwarrior doesn't have (the exact priority,
+//! the blocked status, story points and references) is kept in `myrello_*`
+//! attributes, so a round trip preserves it
+use super::{NewTask, TransferError};
+use crate::op;
+use crate::task;
+use chrono::prelude::*;
+use rusqlite::{Connection, Error};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub entry: String,
+    pub description: String,
+}
+
+/// A task, in the Taskwarrior JSON format
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TwTask {
+    #[serde(default)]
+    pub uuid: String,
+    pub description: String,
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub myrello_priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub myrello_status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub myrello_sp: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub myrello_ref: Option<String>,
+}
+
+/// A Taskwarrior date (UTC, e.g. 20190501T100000Z) as stored in the database
+fn from_tw_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ").ok()
+}
+
+fn to_db_date(date: &str) -> Option<String> {
+    from_tw_date(date).map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn to_tw_date(date: &str) -> Option<String> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|d| d.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Taskwarrior has three priorities: urgent and high are H, low and
+/// miserable are L, normal is not set
+fn to_tw_priority(priority: &str) -> Option<String> {
+    match priority {
+        "urgent" | "high" => Some("H".to_string()),
+        "low" | "miserable" => Some("L".to_string()),
+        _ => None,
+    }
+}
+
+fn from_tw_priority(priority: &str) -> Option<String> {
+    match priority {
+        "H" => Some("high".to_string()),
+        "M" => Some("normal".to_string()),
+        "L" => Some("low".to_string()),
+        _ => None,
+    }
+}
+
+/// Parse a Taskwarrior export: a JSON array or one JSON object per line
+pub fn parse(input: &str) -> Result<Vec<TwTask>, TransferError> {
+    if input.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(input)?);
+    }
+    let mut rv = Vec::new();
+    for line in input.lines().map(|l| l.trim().trim_end_matches(',')) {
+        if !line.is_empty() {
+            rv.push(serde_json::from_str(line)?);
+        }
+    }
+    Ok(rv)
+}
+
+/// Convert the pending, waiting and completed tasks; deleted and recurring
+/// (template) tasks are skipped
+pub fn to_tasks(tasks: &[TwTask]) -> Vec<NewTask> {
+    let mut rv = Vec::new();
+    for t in tasks {
+        let status = match (t.status.as_str(), t.myrello_status.as_ref()) {
+            ("completed", _) => "done",
+            ("pending", Some(status)) | ("waiting", Some(status))
+                if super::check_status(status).is_ok() && status != "done" =>
+            {
+                status.as_str()
+            }
+            ("pending", _) if t.start.is_some() => "in_progress",
+            ("pending", _) => "todo",
+            ("waiting", _) => "block",
+            _ => continue,
+        };
+        let mut labels = Vec::new();
+        if let Some(project) = &t.project {
+            labels.push(format!("project:{}", project));
+        }
+        labels.extend(t.tags.iter().cloned());
+        let note: Vec<&str> = t
+            .annotations
+            .iter()
+            .map(|a| a.description.as_str())
+            .collect();
+        rv.push(NewTask {
+            descr: t.description.clone(),
+            status: status.to_string(),
+            priority: t
+                .myrello_priority
+                .clone()
+                .or_else(|| t.priority.as_ref().and_then(|p| from_tw_priority(p))),
+            storypoints: t.myrello_sp,
+            labels,
+            reference: t.myrello_ref.clone(),
+            note: if note.is_empty() {
+                None
+            } else {
+                Some(note.join("\n"))
+            },
+            due_date: t.due.as_ref().and_then(|d| from_tw_date(d)).map(|d| {
+                Utc.from_utc_datetime(&d)
+                    .with_timezone(&Local)
+                    .naive_local()
+                    .date()
+            }),
+            creation_date: t.entry.as_ref().and_then(|d| to_db_date(d)),
+            start_date: t.start.as_ref().and_then(|d| to_db_date(d)),
+            completion_date: t.end.as_ref().and_then(|d| to_db_date(d)),
+            ..Default::default()
+        });
+    }
+    rv
+}
+
+/// A stable uuid for a task, from its creation date and its id
+fn uuid(t: &op::Task) -> String {
+    let secs = t
+        .creation_date
+        .as_ref()
+        .and_then(|d| NaiveDateTime::parse_from_str(d, "%Y-%m-%d %H:%M:%S").ok())
+        .map_or(0, |d| d.timestamp() as u32);
+    format!("{:08x}-0000-4000-8000-{:012x}", secs, t.id)
+}
+
+/// Convert a task; `start` is the date the task was started, if any
+pub fn to_tw_task(t: &op::Task, start: Option<&str>) -> TwTask {
+    let entry = t.creation_date.as_ref().and_then(|d| to_tw_date(d));
+    let mut rv = TwTask {
+        uuid: uuid(t),
+        description: t.descr.trim().to_string(),
+        status: if t.status == "done" {
+            "completed".to_string()
+        } else {
+            "pending".to_string()
+        },
+        entry: entry.clone(),
+        start: start.and_then(to_tw_date),
+        end: t.completion_date.as_ref().and_then(|d| to_tw_date(d)),
+        due: t
+            .due_date
+            .as_ref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .map(|d| task::local_midnight(d).format("%Y%m%dT%H%M%SZ").to_string()),
+        priority: to_tw_priority(&t.priority),
+        myrello_priority: Some(t.priority.clone()),
+        myrello_sp: Some(t.storypoints).filter(|sp| *sp > 0),
+        myrello_ref: t.reference.clone(),
+        ..Default::default()
+    };
+    if t.status == "block" {
+        rv.myrello_status = Some(t.status.clone());
+    }
+    for l in &t.labels {
+        match task::label_namespace(l) {
+            Some("project") if rv.project.is_none() => {
+                rv.project = Some(l["project:".len()..].to_string())
+            }
+            _ => rv.tags.push(l.clone()),
+        }
+    }
+    if let Some(note) = &t.note {
+        rv.annotations = note
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| Annotation {
+                entry: entry.clone().unwrap_or_default(),
+                description: l.to_string(),
+            })
+            .collect();
+    }
+    rv
+}
+
+/// The open tasks with the labels, and the done ones if requested, as a
+/// Taskwarrior JSON array
+pub fn export(db: &Connection, labels: &[String], done: bool) -> Result<String, Error> {
+    let mut tasks = Vec::new();
+    for t in super::op_tasks(db, labels, done)? {
+        let start = crate::db::get_start_date(db, t.id)?;
+        tasks.push(to_tw_task(&t, start.as_deref()));
+    }
+    Ok(serde_json::to_string_pretty(&tasks).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod taskwarrior_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let array = r#"[{"uuid":"a","description":"one","status":"pending"},
+            {"uuid":"b","description":"two","status":"completed","end":"20190503T120000Z"}]"#;
+        let lines = "{\"uuid\":\"a\",\"description\":\"one\",\"status\":\"pending\"},\n\n\
+                     {\"uuid\":\"b\",\"description\":\"two\",\"status\":\"completed\",\"end\":\"20190503T120000Z\"}\n";
+        assert_eq!(parse(array).unwrap(), parse(lines).unwrap());
+        assert_eq!(parse(array).unwrap().len(), 2);
+        assert!(parse("{").is_err());
+    }
+
+    #[test]
+    fn test_to_tasks() {
+        let input = r#"[
+            {"uuid":"a","description":"garden","status":"pending","entry":"20190501T100000Z",
+             "start":"20190502T080000Z","project":"home","tags":["outdoor"],"priority":"H",
+             "annotations":[{"entry":"20190501T100000Z","description":"buy seeds"},
+                            {"entry":"20190501T110000Z","description":"water"}]},
+            {"uuid":"b","description":"blocked","status":"pending","myrello_status":"block",
+             "myrello_priority":"urgent","myrello_sp":3,"myrello_ref":"https://x.org"},
+            {"uuid":"c","description":"done","status":"completed","end":"20190503T120000Z"},
+            {"uuid":"d","description":"gone","status":"deleted"},
+            {"uuid":"e","description":"later","status":"waiting"}
+        ]"#;
+        let uut = to_tasks(&parse(input).unwrap());
+        assert_eq!(uut.len(), 4);
+        assert_eq!(uut[0].status, "in_progress");
+        assert_eq!(uut[0].labels, vec!["project:home", "outdoor"]);
+        assert_eq!(uut[0].priority, Some("high".to_string()));
+        assert_eq!(uut[0].note, Some("buy seeds\nwater".to_string()));
+        assert_eq!(
+            uut[0].creation_date,
+            Some("2019-05-01 10:00:00".to_string())
+        );
+        assert_eq!(uut[0].start_date, Some("2019-05-02 08:00:00".to_string()));
+        assert_eq!(uut[1].status, "block");
+        assert_eq!(uut[1].priority, Some("urgent".to_string()));
+        assert_eq!(uut[1].storypoints, Some(3));
+        assert_eq!(uut[1].reference, Some("https://x.org".to_string()));
+        assert_eq!(uut[2].status, "done");
+        assert_eq!(
+            uut[2].completion_date,
+            Some("2019-05-03 12:00:00".to_string())
+        );
+        assert_eq!(uut[3].status, "block");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let t = op::Task {
+            id: 4,
+            descr: "garden".to_string(),
+            priority: "miserable".to_string(),
+            status: "block".to_string(),
+            storypoints: 2,
+            labels: vec!["project:home".to_string(), "outdoor".to_string()],
+            reference: Some("https://x.org".to_string()),
+            note: Some("buy seeds\nwater".to_string()),
+            creation_date: Some("2019-05-01 10:00:00".to_string()),
+            ..Default::default()
+        };
+        let tw = to_tw_task(&t, Some("2019-05-02 08:00:00"));
+        assert_eq!(tw.uuid, "5cc96e20-0000-4000-8000-000000000004");
+        assert_eq!(tw.status, "pending");
+        assert_eq!(tw.priority, Some("L".to_string()));
+        assert_eq!(tw.project, Some("home".to_string()));
+        assert_eq!(tw.tags, vec!["outdoor"]);
+        assert_eq!(tw.annotations.len(), 2);
+        let back = &to_tasks(&[tw])[0];
+        assert_eq!(back.descr, t.descr);
+        assert_eq!(back.status, t.status);
+        assert_eq!(back.priority, Some(t.priority.clone()));
+        assert_eq!(back.storypoints, Some(2));
+        assert_eq!(back.labels, t.labels);
+        assert_eq!(back.reference, t.reference);
+        assert_eq!(back.note, t.note);
+        assert_eq!(back.creation_date, t.creation_date);
+        assert_eq!(back.start_date, Some("2019-05-02 08:00:00".to_string()));
+    }
+}
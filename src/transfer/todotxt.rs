@@ -0,0 +1,238 @@
+//! Conversion between todo.txt lines and tasks
+//!
+//! Priorities (A) to (E) are the five priorities, `+project` is a label and
+//! `@context` is a label in the `context` namespace. The extensions `sp:`,
+//! `ref:`, `due:`, `status:` and `pri:` (the priority of a done task) carry
+//! the other attributes
+use super::{NewTask, PRIORITIES};
+use crate::metrics::local_day;
+use crate::op;
+use crate::task;
+use chrono::NaiveDate;
+use rusqlite::{Connection, Error};
+
+fn priority_from_letter(letter: char) -> Option<&'static str> {
+    let index = (letter as usize).checked_sub('A' as usize)?;
+    PRIORITIES.get(index).cloned()
+}
+
+fn priority_to_letter(priority: &str) -> Option<char> {
+    let index = PRIORITIES.iter().position(|p| *p == priority)?;
+    Some((b'A' + index as u8) as char)
+}
+
+fn parse_date(token: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(token, "%Y-%m-%d").ok()
+}
+
+/// A local day as stored in the database: local midnight, in UTC
+fn to_db_date(day: NaiveDate) -> String {
+    task::local_midnight(day)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Spaces are not allowed in the value of an extension
+fn encode(value: &str) -> String {
+    value.replace('%', "%25").replace(' ', "%20")
+}
+
+fn decode(value: &str) -> String {
+    value.replace("%20", " ").replace("%25", "%")
+}
+
+/// Parse a todo.txt line, None for empty lines
+pub fn parse_line(line: &str) -> Option<NewTask> {
+    let mut tokens = line.split_whitespace().peekable();
+    tokens.peek()?;
+    let mut rv = NewTask::default();
+    if tokens.peek() == Some(&"x") {
+        tokens.next();
+        rv.status = "done".to_string();
+        if let Some(date) = tokens.peek().and_then(|t| parse_date(t)) {
+            tokens.next();
+            rv.completion_date = Some(to_db_date(date));
+        }
+    } else if let Some(token) = tokens.peek() {
+        let chars: Vec<char> = token.chars().collect();
+        if chars.len() == 3 && chars[0] == '(' && chars[2] == ')' {
+            if let Some(priority) = priority_from_letter(chars[1]) {
+                tokens.next();
+                rv.priority = Some(priority.to_string());
+            }
+        }
+    }
+    if let Some(date) = tokens.peek().and_then(|t| parse_date(t)) {
+        tokens.next();
+        rv.creation_date = Some(to_db_date(date));
+    }
+    let mut descr = Vec::new();
+    let mut references = Vec::new();
+    for token in tokens {
+        let (key, value) = match token.find(':') {
+            Some(i) => (&token[..i], &token[i + 1..]),
+            None => ("", token),
+        };
+        if token.len() > 1 && token.starts_with('+') {
+            rv.labels.push(token[1..].to_string());
+        } else if token.len() > 1 && token.starts_with('@') {
+            rv.labels.push(format!("context:{}", &token[1..]));
+        } else if key == "sp" && value.parse::<u32>().is_ok() {
+            rv.storypoints = value.parse().ok();
+        } else if key == "ref" && !value.is_empty() {
+            references.push(decode(value));
+        } else if key == "due" && parse_date(value).is_some() {
+            rv.due_date = parse_date(value);
+        } else if key == "status" && super::check_status(value).is_ok() {
+            // the x mark of a done task takes precedence
+            if rv.status != "done" {
+                rv.status = value.to_string();
+            }
+        } else if key == "pri" && value.len() == 1 {
+            rv.priority = value
+                .chars()
+                .next()
+                .and_then(priority_from_letter)
+                .map(str::to_string);
+        } else {
+            descr.push(token);
+        }
+    }
+    rv.descr = descr.join(" ");
+    if !references.is_empty() {
+        rv.reference = Some(references.join("\n"));
+    }
+    Some(rv)
+}
+
+/// Parse a todo.txt file, skipping the empty lines
+pub fn parse(input: &str) -> Vec<NewTask> {
+    input.lines().filter_map(parse_line).collect()
+}
+
+/// A task as a todo.txt line
+pub fn to_line(t: &op::Task) -> String {
+    let mut tokens = Vec::new();
+    let letter = Some(t.priority.as_str())
+        .filter(|p| *p != "normal")
+        .and_then(priority_to_letter);
+    let completion = t.completion_date.as_ref().and_then(|d| local_day(d));
+    match completion {
+        Some(completion) => {
+            tokens.push("x".to_string());
+            tokens.push(completion.format("%Y-%m-%d").to_string());
+        }
+        None => {
+            if let Some(letter) = letter {
+                tokens.push(format!("({})", letter));
+            }
+        }
+    }
+    if let Some(creation) = t.creation_date.as_ref().and_then(|d| local_day(d)) {
+        tokens.push(creation.format("%Y-%m-%d").to_string());
+    }
+    tokens.push(t.descr.trim().to_string());
+    for l in &t.labels {
+        match task::label_namespace(l) {
+            Some("context") => tokens.push(format!("@{}", &l["context:".len()..])),
+            _ => tokens.push(format!("+{}", l.replace(' ', "-"))),
+        }
+    }
+    if t.storypoints > 0 {
+        tokens.push(format!("sp:{}", t.storypoints));
+    }
+    if let Some(due) = &t.due_date {
+        tokens.push(format!("due:{}", due));
+    }
+    if let Some(reference) = &t.reference {
+        for r in reference.lines().map(str::trim).filter(|r| !r.is_empty()) {
+            tokens.push(format!("ref:{}", encode(r)));
+        }
+    }
+    if t.status == "in_progress" || t.status == "block" {
+        tokens.push(format!("status:{}", t.status));
+    }
+    if let (Some(_), Some(letter)) = (completion, letter) {
+        tokens.push(format!("pri:{}", letter));
+    }
+    tokens.join(" ")
+}
+
+/// The open tasks as todo.txt lines, followed by the done ones if requested
+pub fn export(db: &Connection, labels: &[String], done: bool) -> Result<String, Error> {
+    Ok(super::op_tasks(db, labels, done)?
+        .iter()
+        .map(|t| format!("{}\n", to_line(t)))
+        .collect())
+}
+
+#[cfg(test)]
+mod todotxt_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("   "), None);
+        let uut = parse_line(
+            "(B) 2019-05-01 call mum +family @phone sp:2 due:2019-06-01 ref:https://x.org/a%20b url:x",
+        )
+        .unwrap();
+        assert_eq!(uut.priority, Some("high".to_string()));
+        assert_eq!(
+            uut.creation_date,
+            Some(to_db_date(parse_date("2019-05-01").unwrap()))
+        );
+        assert_eq!(uut.descr, "call mum url:x");
+        assert_eq!(uut.labels, vec!["family", "context:phone"]);
+        assert_eq!(uut.storypoints, Some(2));
+        assert_eq!(uut.due_date, parse_date("2019-06-01"));
+        assert_eq!(uut.reference, Some("https://x.org/a b".to_string()));
+        assert_eq!(uut.status, "todo");
+        let uut = parse_line("x 2019-05-03 2019-05-01 release pri:A status:block").unwrap();
+        assert_eq!(uut.status, "done");
+        assert_eq!(uut.priority, Some("urgent".to_string()));
+        assert_eq!(
+            uut.completion_date,
+            Some(to_db_date(parse_date("2019-05-03").unwrap()))
+        );
+        assert_eq!(uut.descr, "release");
+        let uut = parse_line("(Z) xylophone status:in_progress").unwrap();
+        assert_eq!(uut.priority, None);
+        assert_eq!(uut.status, "in_progress");
+        assert_eq!(uut.descr, "(Z) xylophone");
+    }
+
+    #[test]
+    fn test_to_line() {
+        let mut t = op::Task {
+            descr: "call mum ".to_string(),
+            priority: "high".to_string(),
+            status: "in_progress".to_string(),
+            storypoints: 2,
+            labels: vec!["family".to_string(), "context:phone".to_string()],
+            reference: Some("https://x.org/a b\nticket-1".to_string()),
+            due_date: Some("2019-06-01".to_string()),
+            creation_date: Some(to_db_date(parse_date("2019-05-01").unwrap())),
+            ..Default::default()
+        };
+        let line = to_line(&t);
+        assert_eq!(
+            line,
+            "(B) 2019-05-01 call mum +family @phone sp:2 due:2019-06-01 \
+             ref:https://x.org/a%20b ref:ticket-1 status:in_progress"
+        );
+        let back = parse_line(&line).unwrap();
+        assert_eq!(back.descr, "call mum");
+        assert_eq!(back.labels, t.labels);
+        assert_eq!(back.reference, t.reference);
+        assert_eq!(back.creation_date, t.creation_date);
+        t.status = "done".to_string();
+        t.completion_date = Some(to_db_date(parse_date("2019-05-03").unwrap()));
+        t.labels.clear();
+        t.reference = None;
+        assert_eq!(
+            to_line(&t),
+            "x 2019-05-03 2019-05-01 call mum sp:2 due:2019-06-01 pri:B"
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Import of a Trello board, from its JSON export
+//!
+//! Lists are mapped to statuses, labels to labels, checklists to steps, the
+//! card description to the note and the card URL to the reference
+use super::{check_status, NewStep, NewTask, TransferError};
+use chrono::prelude::*;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct List {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Label {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckItem {
+    pub name: String,
+    pub state: String,
+    #[serde(default)]
+    pub pos: f64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checklist {
+    pub id_card: String,
+    pub name: String,
+    #[serde(default)]
+    pub pos: f64,
+    #[serde(default)]
+    pub check_items: Vec<CheckItem>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Member {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Card {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    pub id_list: String,
+    #[serde(default)]
+    pub id_labels: Vec<String>,
+    #[serde(default)]
+    pub id_members: Vec<String>,
+    #[serde(default)]
+    pub closed: bool,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub due: Option<String>,
+    #[serde(default)]
+    pub date_last_activity: Option<String>,
+}
+
+/// The parts of a Trello board export used by the import
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Board {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub lists: Vec<List>,
+    #[serde(default)]
+    pub cards: Vec<Card>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub checklists: Vec<Checklist>,
+    #[serde(default)]
+    pub members: Vec<Member>,
+}
+
+pub fn parse(input: &str) -> Result<Board, TransferError> {
+    Ok(serde_json::from_str(input)?)
+}
+
+/// Guess the status of a list from its name, todo if nothing matches
+pub fn default_status(list: &str) -> &'static str {
+    let list = list.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| list.contains(w));
+    if has(&["done", "complete", "closed", "shipped"]) {
+        "done"
+    } else if has(&["block", "wait", "hold"]) {
+        "block"
+    } else if has(&["doing", "progress", "review", "testing"]) {
+        "in_progress"
+    } else {
+        "todo"
+    }
+}
+
+/// The status of every list, the mapping given by the user takes precedence
+/// on the names of the lists; closed lists are included only with `archived`
+pub fn list_statuses(
+    board: &Board,
+    mapping: &[(String, String)],
+    archived: bool,
+) -> Result<Vec<(String, String)>, TransferError> {
+    let mut rv = Vec::new();
+    for l in board.lists.iter().filter(|l| archived || !l.closed) {
+        let status = match mapping
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(l.name.trim()))
+        {
+            Some((_, status)) => status.clone(),
+            None => default_status(&l.name).to_string(),
+        };
+        check_status(&status)?;
+        rv.push((l.id.clone(), status));
+    }
+    Ok(rv)
+}
+
+/// A Trello label as myrello label: the name, or the color if it has no
+/// name, without spaces
+fn label_name(label: &Label) -> Option<String> {
+    let name = if label.name.trim().is_empty() {
+        label.color.as_ref()?.clone()
+    } else {
+        label.name.clone()
+    };
+    Some(name.split_whitespace().collect::<Vec<_>>().join("-"))
+}
+
+/// The creation date of a card is encoded in the first 8 hex digits of its id
+fn creation_date(card_id: &str) -> Option<String> {
+    let secs = i64::from_str_radix(card_id.get(0..8)?, 16).ok()?;
+    Some(
+        Utc.timestamp(secs, 0)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+    )
+}
+
+fn to_db_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(date)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+/// Convert the cards of the board into new tasks
+/// Cards of lists not in `statuses` are skipped, as archived cards unless
+/// `archived` is set
+pub fn to_tasks(board: &Board, statuses: &[(String, String)], archived: bool) -> Vec<NewTask> {
+    let mut rv = Vec::new();
+    for c in board.cards.iter().filter(|c| archived || !c.closed) {
+        let status = match statuses.iter().find(|(id, _)| *id == c.id_list) {
+            Some((_, status)) => status.clone(),
+            None => continue,
+        };
+        let mut checklists: Vec<&Checklist> = board
+            .checklists
+            .iter()
+            .filter(|cl| cl.id_card == c.id)
+            .collect();
+        checklists.sort_by(|a, b| {
+            a.pos
+                .partial_cmp(&b.pos)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let mut steps = Vec::new();
+        for cl in &checklists {
+            let mut items: Vec<&CheckItem> = cl.check_items.iter().collect();
+            items.sort_by(|a, b| {
+                a.pos
+                    .partial_cmp(&b.pos)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            for i in items {
+                steps.push(NewStep {
+                    descr: if checklists.len() > 1 {
+                        format!("{}: {}", cl.name, i.name)
+                    } else {
+                        i.name.clone()
+                    },
+                    done: i.state == "complete",
+                    ..Default::default()
+                });
+            }
+        }
+        let completion_date = if status == "done" {
+            c.date_last_activity
+                .as_ref()
+                .and_then(|d| to_db_date(d))
+                .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+        } else {
+            None
+        };
+        rv.push(NewTask {
+            descr: c.name.trim().to_string(),
+            status,
+            labels: c
+                .id_labels
+                .iter()
+                .filter_map(|id| board.labels.iter().find(|l| l.id == *id))
+                .filter_map(label_name)
+                .collect(),
+            reference: c.url.clone(),
+            note: if c.desc.trim().is_empty() {
+                None
+            } else {
+                Some(c.desc.clone())
+            },
+            assignee: c
+                .id_members
+                .first()
+                .and_then(|id| board.members.iter().find(|m| m.id == *id))
+                .map(|m| m.username.clone()),
+            due_date: c
+                .due
+                .as_ref()
+                .and_then(|d| to_db_date(d))
+                .map(|d| d.with_timezone(&Local).date().naive_local()),
+            steps,
+            creation_date: creation_date(&c.id),
+            completion_date,
+            ..Default::default()
+        });
+    }
+    rv
+}
+
+#[cfg(test)]
+mod trello_tests {
+    use super::*;
+    use crate::{db, transfer};
+    use assert_fs::prelude::*;
+    use assert_fs::TempDir;
+
+    const BOARD: &str = r#"{
+        "name": "myboard",
+        "lists": [
+            { "id": "l1", "name": "To Do", "closed": false },
+            { "id": "l2", "name": "Doing", "closed": false },
+            { "id": "l3", "name": "Done", "closed": false },
+            { "id": "l4", "name": "Icebox", "closed": true }
+        ],
+        "labels": [
+            { "id": "b1", "name": "bug", "color": "red" },
+            { "id": "b2", "name": "", "color": "green" },
+            { "id": "b3", "name": "big feature", "color": "blue" }
+        ],
+        "members": [ { "id": "m1", "username": "alice" } ],
+        "cards": [
+            {
+                "id": "5cd1a2b0aaaaaaaaaaaaaaaa", "name": "fix the crash ", "desc": "it crashes",
+                "idList": "l2", "idLabels": ["b1", "b2"], "idMembers": ["m1"],
+                "closed": false, "url": "https://trello.com/c/abc/1-fix-the-crash",
+                "due": "2019-06-01T10:00:00.000Z"
+            },
+            {
+                "id": "5cd1a2b0bbbbbbbbbbbbbbbb", "name": "release", "desc": "",
+                "idList": "l3", "idLabels": ["b3"], "closed": false,
+                "dateLastActivity": "2019-05-08T09:30:00.000Z"
+            },
+            { "id": "5cd1a2b0cccccccccccccccc", "name": "archived", "idList": "l1", "closed": true },
+            { "id": "5cd1a2b0dddddddddddddddd", "name": "in the icebox", "idList": "l4" }
+        ],
+        "checklists": [
+            {
+                "id": "c3", "idCard": "5cd1a2b0bbbbbbbbbbbbbbbb", "name": "Go", "pos": 1,
+                "checkItems": [ { "name": "tag", "state": "incomplete", "pos": 1 } ]
+            },
+            {
+                "id": "c2", "idCard": "5cd1a2b0aaaaaaaaaaaaaaaa", "name": "Later", "pos": 2,
+                "checkItems": [ { "name": "release", "state": "incomplete", "pos": 1 } ]
+            },
+            {
+                "id": "c1", "idCard": "5cd1a2b0aaaaaaaaaaaaaaaa", "name": "Fix", "pos": 1,
+                "checkItems": [
+                    { "name": "test", "state": "incomplete", "pos": 2 },
+                    { "name": "reproduce", "state": "complete", "pos": 1 }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_default_status() {
+        assert_eq!(default_status("To Do"), "todo");
+        assert_eq!(default_status("Doing"), "in_progress");
+        assert_eq!(default_status("Code review"), "in_progress");
+        assert_eq!(default_status("Blocked"), "block");
+        assert_eq!(default_status("Done 🎉"), "done");
+    }
+
+    #[test]
+    fn test_list_statuses() {
+        let board = parse(BOARD).unwrap();
+        let mapping = vec![("to do".to_string(), "block".to_string())];
+        let uut = list_statuses(&board, &mapping, false).unwrap();
+        assert_eq!(
+            uut,
+            vec![
+                ("l1".to_string(), "block".to_string()),
+                ("l2".to_string(), "in_progress".to_string()),
+                ("l3".to_string(), "done".to_string()),
+            ]
+        );
+        assert_eq!(list_statuses(&board, &[], true).unwrap().len(), 4);
+        let mapping = vec![("Done".to_string(), "finished".to_string())];
+        assert!(list_statuses(&board, &mapping, false).is_err());
+    }
+
+    #[test]
+    fn test_to_tasks() {
+        let board = parse(BOARD).unwrap();
+        let statuses = list_statuses(&board, &[], false).unwrap();
+        let uut = to_tasks(&board, &statuses, false);
+        assert_eq!(uut.len(), 2);
+        assert_eq!(uut[0].descr, "fix the crash");
+        assert_eq!(uut[0].status, "in_progress");
+        assert_eq!(uut[0].labels, vec!["bug", "green"]);
+        assert_eq!(
+            uut[0].reference,
+            Some("https://trello.com/c/abc/1-fix-the-crash".to_string())
+        );
+        assert_eq!(uut[0].note, Some("it crashes".to_string()));
+        assert_eq!(uut[0].assignee, Some("alice".to_string()));
+        assert_eq!(
+            uut[0].steps,
+            vec![
+                NewStep {
+                    descr: "Fix: reproduce".to_string(),
+                    done: true,
+                    ..Default::default()
+                },
+                NewStep {
+                    descr: "Fix: test".to_string(),
+                    done: false,
+                    ..Default::default()
+                },
+                NewStep {
+                    descr: "Later: release".to_string(),
+                    done: false,
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            uut[0].creation_date,
+            Some("2019-05-07 15:22:24".to_string())
+        );
+        assert_eq!(uut[1].labels, vec!["big-feature"]);
+        assert_eq!(uut[1].note, None);
+        assert_eq!(
+            uut[1].completion_date,
+            Some("2019-05-08 09:30:00".to_string())
+        );
+        assert_eq!(to_tasks(&board, &statuses, true).len(), 3);
+    }
+
+    #[test]
+    fn test_create_tasks() {
+        let temp = TempDir::new().unwrap();
+        let dbfile = temp.child("dbtest");
+        db::init(dbfile.path(), true).unwrap();
+        let mut db = db::get_db(dbfile.path()).unwrap();
+        let board = parse(BOARD).unwrap();
+        let statuses = list_statuses(&board, &[], false).unwrap();
+        let ids = transfer::create_tasks(&mut db, &to_tasks(&board, &statuses, false)).unwrap();
+        // the steps of the done card are completed with it, not at the import
+        let steps = db::get_all_steps(&db, ids[1]).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].completion_date, "2019-05-08 09:30:00");
+        // never started, the start step stays open
+        assert_eq!(db::get_start_date(&db, ids[1]).unwrap(), None);
+    }
+}